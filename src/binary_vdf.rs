@@ -0,0 +1,195 @@
+//! Steam's binary KeyValues format, as used by `userdata/<id>/config/shortcuts.vdf`.
+
+use bstr::BString;
+use bstr::ByteSlice;
+
+const MAP: u8 = 0x00;
+const STRING: u8 = 0x01;
+const INT: u8 = 0x02;
+const FLOAT: u8 = 0x03;
+const INT64: u8 = 0x07;
+const END: u8 = 0x08;
+
+/// A binary VDF value. Maps keep their entries in file order so that rewriting a file we
+/// didn't create only changes what we meant to change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Map(Vec<(BString, Value)>),
+    String(BString),
+    Int(u32),
+    Float(f32),
+    Int64(u64),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key.as_bytes()))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Sets `key` in a map, replacing any existing value with the same key.
+    pub fn set(&mut self, key: &str, value: Value) {
+        let Value::Map(entries) = self else {
+            panic!("can't set {key:?} on a non-map value");
+        };
+        match entries
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key.as_bytes()))
+        {
+            Some((_, existing)) => *existing = value,
+            None => entries.push((key.into(), value)),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => s.to_str().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<u32> {
+        match self {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_map_mut(&mut self) -> Option<&mut Vec<(BString, Value)>> {
+        match self {
+            Value::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a whole file, which is a single map whose end marker is the end of the file.
+pub fn parse(bytes: &[u8]) -> Result<Value, String> {
+    let mut rest = bytes;
+    let value = parse_map(&mut rest)?;
+    Ok(value)
+}
+
+fn parse_map(rest: &mut &[u8]) -> Result<Value, String> {
+    let mut entries = Vec::new();
+    loop {
+        let Some((&kind, tail)) = rest.split_first() else {
+            // Top-level maps sometimes omit their final end marker.
+            return Ok(Value::Map(entries));
+        };
+        *rest = tail;
+        if kind == END {
+            return Ok(Value::Map(entries));
+        }
+        let key = parse_cstr(rest)?;
+        let value = match kind {
+            MAP => parse_map(rest)?,
+            STRING => Value::String(parse_cstr(rest)?),
+            INT => Value::Int(u32::from_le_bytes(take(rest)?)),
+            FLOAT => Value::Float(f32::from_le_bytes(take(rest)?)),
+            INT64 => Value::Int64(u64::from_le_bytes(take(rest)?)),
+            other => return Err(format!("unknown binary VDF type {other:#04x} for {key:?}")),
+        };
+        entries.push((key, value));
+    }
+}
+
+fn parse_cstr(rest: &mut &[u8]) -> Result<BString, String> {
+    let end = rest
+        .find_byte(0)
+        .ok_or_else(|| "unterminated string in binary VDF".to_string())?;
+    let s = BString::from(&rest[..end]);
+    *rest = &rest[end + 1..];
+    Ok(s)
+}
+
+fn take<const N: usize>(rest: &mut &[u8]) -> Result<[u8; N], String> {
+    if rest.len() < N {
+        return Err("truncated number in binary VDF".to_string());
+    }
+    let (head, tail) = rest.split_at(N);
+    *rest = tail;
+    Ok(head.try_into().unwrap())
+}
+
+/// Serializes a top-level map, the inverse of [`parse`].
+pub fn serialize(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let Value::Map(entries) = value else {
+        panic!("binary VDF files must contain a map");
+    };
+    serialize_entries(entries, &mut bytes);
+    bytes
+}
+
+fn serialize_entries(entries: &[(BString, Value)], bytes: &mut Vec<u8>) {
+    for (key, value) in entries {
+        let kind = match value {
+            Value::Map(_) => MAP,
+            Value::String(_) => STRING,
+            Value::Int(_) => INT,
+            Value::Float(_) => FLOAT,
+            Value::Int64(_) => INT64,
+        };
+        bytes.push(kind);
+        bytes.extend_from_slice(key);
+        bytes.push(0);
+        match value {
+            Value::Map(entries) => serialize_entries(entries, bytes),
+            Value::String(s) => {
+                bytes.extend_from_slice(s);
+                bytes.push(0);
+            }
+            Value::Int(i) => bytes.extend_from_slice(&i.to_le_bytes()),
+            Value::Float(f) => bytes.extend_from_slice(&f.to_le_bytes()),
+            Value::Int64(i) => bytes.extend_from_slice(&i.to_le_bytes()),
+        }
+    }
+    bytes.push(END);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `shortcuts.vdf` laid out the way Steam writes it, with two non-Steam games.
+    const SHORTCUTS: &[u8] = include_bytes!("../testdata/shortcuts.vdf");
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        let value = parse(SHORTCUTS).unwrap();
+        assert_eq!(serialize(&value), SHORTCUTS);
+    }
+
+    #[test]
+    fn reads_entries() {
+        let value = parse(SHORTCUTS).unwrap();
+        let firefox = value.get("shortcuts").unwrap().get("0").unwrap();
+        assert_eq!(
+            firefox.get("AppName").and_then(Value::as_str),
+            Some("Firefox")
+        );
+        assert_eq!(
+            firefox.get("appid").and_then(Value::as_int),
+            Some(0xA7E2_11C3)
+        );
+        assert_eq!(
+            firefox
+                .get("tags")
+                .unwrap()
+                .get("0")
+                .and_then(Value::as_str),
+            Some("favorite")
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        assert!(parse(&SHORTCUTS[..SHORTCUTS.len() / 2]).is_err());
+    }
+}
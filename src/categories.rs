@@ -0,0 +1,430 @@
+//! Speedrun categories, and each one's sum of bests from a save's per-chapter bests.
+//!
+//! The game records only the best run of each chapter side and the best full clear of each
+//! A-side, so a category's sum of bests adds those up. It can't know, say, the fastest 1A that
+//! also collected every berry.
+
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::celeste_stats::{SideStatsCommon, Stats, World, World::*};
+use crate::config::Config;
+
+/// The chapters with B-sides and C-sides, in order.
+pub const CHAPTERS: [World; 8] = [
+    ForsakenCity,
+    OldSite,
+    CelestialResort,
+    GoldenRidge,
+    MirrorTemple,
+    Reflection,
+    TheSummit,
+    Core,
+];
+
+/// Which of a chapter's recorded bests a leg uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    A,
+    /// The A-side with every berry, the cassette and the crystal heart.
+    FullClear,
+    B,
+    C,
+}
+
+/// One chapter of a category's route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Leg {
+    pub world: World,
+    pub side: Side,
+}
+
+/// Things a category has to collect along its route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Collectible {
+    Berries,
+    Cassettes,
+    Hearts,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    pub legs: Vec<Leg>,
+    /// How many of each collectible the category needs.
+    pub collect: Vec<(Collectible, u32)>,
+}
+
+/// A category's sum of bests in one save, and how much of the category has been done.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryStats {
+    pub name: String,
+    pub legs: u32,
+    /// How many legs have a recorded best.
+    pub legs_done: u32,
+    /// The sums over the legs done so far. Dashes and deaths are missing if any leg is a full
+    /// clear, since the game doesn't record them for full clears.
+    pub time: Duration,
+    pub dashes: Option<u32>,
+    pub deaths: Option<u32>,
+    pub collected: Vec<Progress>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Progress {
+    pub collectible: Collectible,
+    pub have: u32,
+    pub need: u32,
+}
+
+impl CategoryStats {
+    pub fn complete(&self) -> bool {
+        self.legs_done == self.legs && self.collected.iter().all(|p| p.have >= p.need)
+    }
+
+    pub fn started(&self) -> bool {
+        self.legs_done > 0 || self.collected.iter().any(|p| p.have > 0)
+    }
+}
+
+impl std::fmt::Display for Collectible {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Collectible::Berries => "red berries",
+            Collectible::Cassettes => "cassettes",
+            Collectible::Hearts => "hearts",
+        })
+    }
+}
+
+impl Category {
+    /// The well-known categories, followed by any custom ones from `saves.category`.
+    pub fn all(config: &Config) -> Vec<Category> {
+        let mut categories = Category::well_known();
+        for definition in &config.categories {
+            match Category::parse(definition) {
+                Ok(category) => categories.push(category),
+                Err(err) => warn!("Ignoring category {definition:?}: {err}"),
+            }
+        }
+        categories
+    }
+
+    pub fn well_known() -> Vec<Category> {
+        let legs = |worlds: &[World], side: Side| {
+            worlds
+                .iter()
+                .map(|&world| Leg { world, side })
+                .collect::<Vec<_>>()
+        };
+        // Reflection has no berries, so it has nothing to clear fully for them.
+        let berry_legs = CHAPTERS
+            .iter()
+            .map(|&world| Leg {
+                world,
+                side: if world.red_berries() > 0 {
+                    Side::FullClear
+                } else {
+                    Side::A
+                },
+            })
+            .collect::<Vec<_>>();
+        let red_berries = CHAPTERS.iter().map(|world| world.red_berries()).sum();
+
+        let category = |name: &str, legs: Vec<Leg>, collect: Vec<(Collectible, u32)>| Category {
+            name: name.to_string(),
+            legs,
+            collect,
+        };
+        vec![
+            category("Any%", legs(&CHAPTERS[..7], Side::A), vec![]),
+            category(
+                "All Red Berries",
+                berry_legs,
+                vec![(Collectible::Berries, red_berries)],
+            ),
+            // Core's last door needs four crystal hearts.
+            category(
+                "True Ending",
+                legs(&CHAPTERS, Side::A),
+                vec![(Collectible::Hearts, 4)],
+            ),
+            category(
+                "All Cassettes",
+                legs(&CHAPTERS, Side::A),
+                vec![(Collectible::Cassettes, 8)],
+            ),
+            category(
+                "All Hearts",
+                [
+                    legs(&CHAPTERS, Side::A),
+                    legs(&CHAPTERS, Side::B),
+                    legs(&CHAPTERS, Side::C),
+                ]
+                .concat(),
+                vec![(Collectible::Hearts, 24)],
+            ),
+            category(
+                "100%",
+                [
+                    legs(&CHAPTERS, Side::FullClear),
+                    legs(&CHAPTERS, Side::B),
+                    legs(&CHAPTERS, Side::C),
+                    legs(&[Farewell], Side::A),
+                ]
+                .concat(),
+                vec![
+                    (Collectible::Berries, red_berries),
+                    (Collectible::Cassettes, 8),
+                    (Collectible::Hearts, 24),
+                ],
+            ),
+            category("All B-Sides", legs(&CHAPTERS, Side::B), vec![]),
+            category("All C-Sides", legs(&CHAPTERS, Side::C), vec![]),
+        ]
+    }
+
+    /// Parses a custom category like `Early Game: 0A 1A 2FC 3B`, naming each leg by its
+    /// chapter number (0 for the Prologue, 8 for Core, 9 for Farewell) and `A`, `B`, `C`, or
+    /// `FC` for a full clear.
+    pub fn parse(definition: &str) -> Result<Category, String> {
+        let (name, legs) = definition
+            .split_once(':')
+            .ok_or("expected a name, a colon, then the legs")?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("the name is empty".to_string());
+        }
+        let legs = legs
+            .split_whitespace()
+            .map(Leg::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if legs.is_empty() {
+            return Err("there are no legs".to_string());
+        }
+        Ok(Category {
+            name: name.to_string(),
+            legs,
+            collect: Vec::new(),
+        })
+    }
+
+    pub fn sum_of_bests(&self, stats: &Stats) -> CategoryStats {
+        let bests = self
+            .legs
+            .iter()
+            .filter_map(|leg| leg.best(stats))
+            .collect::<Vec<_>>();
+        let sum = |count: fn(&Best) -> Option<u32>| bests.iter().map(count).sum();
+
+        CategoryStats {
+            name: self.name.clone(),
+            legs: self.legs.len() as u32,
+            legs_done: bests.len() as u32,
+            time: bests.iter().map(|best| best.time).sum(),
+            dashes: sum(|best| best.dashes),
+            deaths: sum(|best| best.deaths),
+            collected: self
+                .collect
+                .iter()
+                .map(|&(collectible, need)| Progress {
+                    collectible,
+                    have: collectible.count(stats),
+                    need,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A leg's recorded best.
+#[derive(Debug, Clone, Copy)]
+pub struct Best {
+    pub time: Duration,
+    pub dashes: Option<u32>,
+    pub deaths: Option<u32>,
+}
+
+impl Leg {
+    /// Parses a leg like `1A`, `8C` or `2FC`, as in [`Category::parse`].
+    pub fn parse(leg: &str) -> Result<Leg, String> {
+        let split = leg
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("{leg:?} has no side"))?;
+        let (number, side) = leg.split_at(split);
+        let world = match number.parse::<u32>() {
+            Ok(0) => Prologue,
+            Ok(8) => Core,
+            Ok(9) => Farewell,
+            Ok(number @ 1..=7) => World::try_from(number).map_err(|n| n.to_string())?,
+            _ => return Err(format!("{leg:?} isn't a chapter from 0 to 9")),
+        };
+        let side = match side.to_ascii_uppercase().as_str() {
+            "A" => Side::A,
+            "FC" => Side::FullClear,
+            "B" => Side::B,
+            "C" => Side::C,
+            _ => return Err(format!("{leg:?} should end in A, B, C or FC")),
+        };
+        Ok(Leg { world, side })
+    }
+
+    /// The leg as [`parse`](Self::parse) reads it, like `2FC`.
+    pub fn code(&self) -> String {
+        let number = match self.world {
+            Core => 8,
+            Farewell => 9,
+            world => u32::from(world),
+        };
+        let side = match self.side {
+            Side::A => "A",
+            Side::FullClear => "FC",
+            Side::B => "B",
+            Side::C => "C",
+        };
+        format!("{number}{side}")
+    }
+
+    /// A readable name, like `Forsaken City B-Side`.
+    pub fn name(&self) -> String {
+        let name = self.world.name().trim();
+        let chapter = name.split_once(".  ").map_or(name, |(_, chapter)| chapter);
+        match self.side {
+            Side::A => chapter.to_string(),
+            Side::FullClear => format!("{chapter} Full Clear"),
+            Side::B => format!("{chapter} B-Side"),
+            Side::C => format!("{chapter} C-Side"),
+        }
+    }
+
+    /// The leg's best recorded time, if it has one.
+    pub fn time(&self, stats: &Stats) -> Option<Duration> {
+        self.best(stats).map(|best| best.time)
+    }
+
+    /// The leg's best recorded run, if it has one. Full clears have no dashes or deaths.
+    pub fn best(&self, stats: &Stats) -> Option<Best> {
+        let world = stats.worlds.iter().find(|w| w.world == self.world)?;
+        let run = |common: &SideStatsCommon| {
+            Some(Best {
+                time: common.single_run?,
+                dashes: common.fewest_dashes,
+                deaths: common.fewest_deaths,
+            })
+        };
+        match self.side {
+            Side::A => run(&world.a_side.common),
+            Side::B => run(&world.b_side.common),
+            Side::C => run(&world.c_side.common),
+            Side::FullClear => Some(Best {
+                time: world.a_side.full_clear?,
+                dashes: None,
+                deaths: None,
+            }),
+        }
+    }
+}
+
+impl Collectible {
+    /// How many the save has from the chapters with B-sides and C-sides. Beating a B-side or
+    /// C-side always earns its crystal heart.
+    fn count(self, stats: &Stats) -> u32 {
+        let chapters = stats
+            .worlds
+            .iter()
+            .filter(|world| CHAPTERS.contains(&world.world));
+        match self {
+            Collectible::Berries => chapters.map(|world| world.red_berries()).sum(),
+            Collectible::Cassettes => chapters.filter(|world| world.a_side.cassette).count() as u32,
+            Collectible::Hearts => chapters
+                .map(|world| {
+                    [
+                        world.a_side.heart,
+                        world.b_side.common.completed,
+                        world.c_side.common.completed,
+                    ]
+                    .into_iter()
+                    .filter(|&heart| heart)
+                    .count() as u32
+                })
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::celeste_stats::CelesteStats;
+    use crate::game_stats::GameStats;
+    use crate::testutils;
+
+    #[test]
+    fn parses_categories() {
+        let category = Category::parse(" Early Game : 0A 1a 2FC 3B 8C 9A").unwrap();
+        assert_eq!(category.name, "Early Game");
+        assert_eq!(
+            category.legs.iter().map(Leg::code).collect::<Vec<_>>(),
+            ["0A", "1A", "2FC", "3B", "8C", "9A"]
+        );
+        assert_eq!(category.legs[1].world, ForsakenCity);
+        assert_eq!(category.legs[4].world, Core);
+        assert_eq!(category.legs[5].world, Farewell);
+        assert_eq!(category.legs[2].name(), "Old Site Full Clear");
+        assert!(category.collect.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_categories() {
+        for (definition, error) in [
+            ("1A 2A", "expected a name, a colon, then the legs"),
+            (": 1A", "the name is empty"),
+            ("Nothing:", "there are no legs"),
+            ("Late: 10A", r#""10A" isn't a chapter from 0 to 9"#),
+            ("Late: 7", r#""7" has no side"#),
+            ("Late: 7D", r#""7D" should end in A, B, C or FC"#),
+            ("Late: A", r#""A" isn't a chapter from 0 to 9"#),
+        ] {
+            assert_eq!(Category::parse(definition), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn sums_bests() {
+        // Forsaken City in 1m30s and Old Site in 1m, each with 3 dashes and 4 deaths.
+        let xml = testutils::save_xml(
+            "Madeline",
+            &[(1, 900_000_000, &["r0:0", "r1:1"]), (2, 600_000_000, &[])],
+        );
+        let stats = CelesteStats::new(&Config::default()).parse(&xml).unwrap();
+
+        let category = Category::parse("Start: 1A 2A 3A").unwrap();
+        let sum = category.sum_of_bests(&stats);
+        assert_eq!((sum.legs, sum.legs_done), (3, 2));
+        assert_eq!(sum.time, Duration::from_secs(150));
+        assert_eq!((sum.dashes, sum.deaths), (Some(6), Some(8)));
+        assert!(sum.started() && !sum.complete());
+
+        // The save has no full clears, so the leg doesn't count.
+        let sum = Category::parse("Clear: 1FC 2A")
+            .unwrap()
+            .sum_of_bests(&stats);
+        assert_eq!((sum.legs_done, sum.time), (1, Duration::from_secs(60)));
+
+        let berries = Category::well_known()
+            .into_iter()
+            .find(|category| category.name == "All Red Berries")
+            .unwrap()
+            .sum_of_bests(&stats);
+        assert_eq!(berries.legs_done, 0);
+        assert_eq!(
+            berries
+                .collected
+                .iter()
+                .map(|progress| (progress.have, progress.need))
+                .collect::<Vec<_>>(),
+            [(2, 175)]
+        );
+    }
+}
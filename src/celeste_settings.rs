@@ -0,0 +1,344 @@
+use minidom::Element;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use tracing_unwrap::ResultExt;
+
+use crate::{game_stats::GameStats, stringutils::*};
+
+/// Settings the game changes by itself, which aren't worth reporting.
+const VOLATILE: &[&str] = &["LastSaveFile"];
+
+/// Renders Celeste's `settings.celeste`, and describes what changed in it between syncs.
+pub struct CelesteSettings;
+
+/// The settings that change how the game feels to play, and every control binding. Anything
+/// else the file holds is kept in `other`, so no change goes unnoticed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub version: Option<String>,
+    pub fullscreen: Option<bool>,
+    pub window_scale: Option<u32>,
+    pub vsync: Option<bool>,
+    pub screen_shake: Option<String>,
+    pub disable_flashes: Option<bool>,
+    pub rumble: Option<String>,
+    pub grab_mode: Option<String>,
+    pub crouch_dash_mode: Option<String>,
+    pub speedrun_clock: Option<String>,
+    pub music_volume: Option<u32>,
+    pub sfx_volume: Option<u32>,
+    pub language: Option<String>,
+    /// Each action's bindings, such as `Jump` or `Grab`.
+    pub bindings: BTreeMap<String, Binding>,
+    pub other: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub keyboard: Vec<String>,
+    pub controller: Vec<String>,
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let list = |inputs: &[String]| match inputs {
+            [] => "none".to_string(),
+            inputs => inputs.join(", "),
+        };
+        write!(
+            f,
+            "keyboard {}; controller {}",
+            list(&self.keyboard),
+            list(&self.controller)
+        )
+    }
+}
+
+impl Settings {
+    /// Reads a `Settings` element, leaving anything missing or unreadable unset.
+    pub fn from_save(settings: &Element) -> Option<Self> {
+        if settings.name() != "Settings" {
+            return None;
+        }
+
+        let mut parsed = Settings::default();
+        for el in settings.children() {
+            let text = el.text();
+            let text = text.trim();
+            match el.name() {
+                "Version" => parsed.version = Some(text.to_string()),
+                "Fullscreen" => parsed.fullscreen = text.parse().ok(),
+                "WindowScale" => parsed.window_scale = text.parse().ok(),
+                "VSync" => parsed.vsync = text.parse().ok(),
+                "ScreenShake" => parsed.screen_shake = Some(text.to_string()),
+                "DisableFlashes" => parsed.disable_flashes = text.parse().ok(),
+                "Rumble" => parsed.rumble = Some(text.to_string()),
+                "GrabMode" => parsed.grab_mode = Some(text.to_string()),
+                "CrouchDashMode" => parsed.crouch_dash_mode = Some(text.to_string()),
+                "SpeedrunClock" => parsed.speedrun_clock = Some(text.to_string()),
+                "MusicVolume" => parsed.music_volume = text.parse().ok(),
+                "SFXVolume" => parsed.sfx_volume = text.parse().ok(),
+                "Language" => parsed.language = Some(text.to_string()),
+                name if el.children().next().is_some() => {
+                    parsed
+                        .bindings
+                        .insert(name.to_string(), Binding::from_save(el));
+                }
+                name => {
+                    parsed.other.insert(name.to_string(), text.to_string());
+                }
+            }
+        }
+        Some(parsed)
+    }
+
+    /// Every setting as a label and value, grouped into sections, in display order.
+    fn sections(&self) -> Vec<(&'static str, Vec<(String, String)>)> {
+        fn show<T: ToString>(value: &Option<T>) -> String {
+            value
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "-".to_string())
+        }
+        fn on_off(value: &Option<bool>) -> String {
+            match value {
+                Some(true) => "on".to_string(),
+                Some(false) => "off".to_string(),
+                None => "-".to_string(),
+            }
+        }
+
+        let entry = |label: &str, value: String| (label.to_string(), value);
+        vec![
+            (
+                "Gameplay",
+                vec![
+                    entry("Grab mode", show(&self.grab_mode)),
+                    entry("Crouch dash mode", show(&self.crouch_dash_mode)),
+                    entry("Speedrun clock", show(&self.speedrun_clock)),
+                    entry("Screen shake", show(&self.screen_shake)),
+                    entry("Rumble", show(&self.rumble)),
+                    entry("Disable flashes", on_off(&self.disable_flashes)),
+                ],
+            ),
+            (
+                "Video",
+                vec![
+                    entry("Fullscreen", on_off(&self.fullscreen)),
+                    entry("Window scale", show(&self.window_scale)),
+                    entry("VSync", on_off(&self.vsync)),
+                ],
+            ),
+            (
+                "Audio",
+                vec![
+                    entry("Music volume", show(&self.music_volume)),
+                    entry("SFX volume", show(&self.sfx_volume)),
+                    entry("Language", show(&self.language)),
+                ],
+            ),
+            (
+                "Controls",
+                self.bindings
+                    .iter()
+                    .map(|(action, binding)| (action.clone(), binding.to_string()))
+                    .collect(),
+            ),
+            (
+                "Other",
+                self.other
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+            ),
+        ]
+    }
+}
+
+impl Binding {
+    fn from_save(el: &Element) -> Self {
+        let mut binding = Binding::default();
+        for child in el.children() {
+            let inputs = child
+                .children()
+                .map(|input| input.text().trim().to_string())
+                .filter(|input| !input.is_empty());
+            match child.name() {
+                "Keyboard" => binding.keyboard.extend(inputs),
+                "Controller" => binding.controller.extend(inputs),
+                _ => {}
+            }
+        }
+        binding
+    }
+}
+
+impl GameStats for CelesteSettings {
+    type Model = Settings;
+
+    fn recognizes(&self, file_name: &str, contents: &str) -> bool {
+        file_name == "settings.celeste" && contents.contains("<Settings")
+    }
+
+    fn parse(&self, contents: &str) -> Option<Settings> {
+        let root = contents.parse::<Element>().ok()?;
+        Settings::from_save(&root)
+    }
+
+    fn render_html(&self, settings: &Settings) -> String {
+        let mut output = String::new();
+        output.push_str(include_str!("template.html"));
+        output.push_str(&settings_table(settings));
+        output
+    }
+
+    fn render_text(&self, settings: &Settings) -> String {
+        settings_table(settings).strip_tags()
+    }
+
+    fn highlights(&self, before: Option<&Settings>, after: &Settings) -> Vec<String> {
+        // A first sync has nothing to compare with, and listing every setting isn't news.
+        let Some(before) = before else {
+            return Vec::new();
+        };
+
+        let mut changes = Vec::new();
+        let before = before.sections();
+        for (section, entries) in after.sections() {
+            let previous = before
+                .iter()
+                .find(|(previous, _)| *previous == section)
+                .map(|(_, entries)| entries.as_slice())
+                .unwrap_or_default();
+            for (label, value) in &entries {
+                if VOLATILE.contains(&label.as_str()) {
+                    continue;
+                }
+                match previous.iter().find(|(previous, _)| previous == label) {
+                    Some((_, old)) if old == value => {}
+                    Some((_, old)) => changes.push(format!("Setting {label}: {old} → {value}")),
+                    None => changes.push(format!("Setting {label}: {value}")),
+                }
+            }
+            for (label, old) in previous {
+                if !entries.iter().any(|(current, _)| current == label) {
+                    changes.push(format!("Setting {label}: {old} → removed"));
+                }
+            }
+        }
+        changes
+    }
+}
+
+fn settings_table(settings: &Settings) -> String {
+    let mut output = String::new();
+    output.push_str("<pre>");
+
+    writeln!(
+        &mut output,
+        " {} {}",
+        "Settings".underline().color(White),
+        settings
+            .version
+            .as_deref()
+            .map(|version| format!("v{version}"))
+            .unwrap_or_default()
+            .color(DarkGray)
+    )
+    .unwrap_or_log();
+
+    for (section, entries) in settings.sections() {
+        if entries.is_empty() {
+            continue;
+        }
+        writeln!(
+            &mut output,
+            "{}",
+            format!("  {section:<69}").color(Black).background(White)
+        )
+        .unwrap_or_log();
+        for (label, value) in entries {
+            let label = html_escape::encode_text(&label);
+            let value = html_escape::encode_text(&value);
+            writeln!(
+                &mut output,
+                "  {label:<18} {}",
+                value.to_string().color(White)
+            )
+            .unwrap_or_log();
+        }
+    }
+
+    output.push_str("</pre>");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: &str = include_str!("../testdata/settings.celeste");
+
+    fn settings() -> Settings {
+        CelesteSettings.parse(SETTINGS).unwrap()
+    }
+
+    #[test]
+    fn parses_settings() {
+        assert!(CelesteSettings.recognizes("settings.celeste", SETTINGS));
+        assert!(!CelesteSettings.recognizes("0.celeste", SETTINGS));
+        let settings = settings();
+        assert_eq!(settings.version.as_deref(), Some("1.4.0.0"));
+        assert_eq!(settings.fullscreen, Some(false));
+        assert_eq!(settings.window_scale, Some(6));
+        assert_eq!(settings.screen_shake.as_deref(), Some("Weak"));
+        assert_eq!(settings.sfx_volume, Some(8));
+        assert_eq!(settings.grab_mode.as_deref(), Some("Hold"));
+        assert_eq!(
+            settings.bindings["Jump"],
+            Binding {
+                keyboard: vec!["C".to_string()],
+                controller: vec!["A".to_string(), "Y".to_string()],
+            }
+        );
+        assert_eq!(
+            settings.bindings["Grab"].to_string(),
+            "keyboard none; controller LeftShoulder"
+        );
+        assert_eq!(settings.bindings.len(), 3);
+        assert_eq!(settings.other["VariantsUnlocked"], "true");
+        assert!(CelesteSettings.parse(r#"<Options xmlns="" />"#).is_none());
+    }
+
+    #[test]
+    fn highlights_changed_settings() {
+        let before = settings();
+        assert_eq!(
+            CelesteSettings.highlights(None, &before),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            CelesteSettings.highlights(Some(&before), &before),
+            Vec::<String>::new()
+        );
+
+        let mut after = before.clone();
+        after.fullscreen = Some(true);
+        after.bindings.get_mut("Jump").unwrap().keyboard = vec!["Space".to_string()];
+        after.bindings.remove("Grab");
+        after
+            .other
+            .insert("LastSaveFile".to_string(), "2".to_string());
+        after.other.insert("Mods".to_string(), "on".to_string());
+        assert_eq!(
+            CelesteSettings.highlights(Some(&before), &after),
+            [
+                "Setting Fullscreen: off → on",
+                "Setting Jump: keyboard C; controller A, Y → keyboard Space; controller A, Y",
+                "Setting Grab: keyboard none; controller LeftShoulder → removed",
+                "Setting Mods: on",
+            ]
+        );
+    }
+}
@@ -1,11 +1,17 @@
+use itertools::Itertools;
 use minidom::Element;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::Write;
-use std::{collections::BTreeSet, convert::TryFrom, env, string::ToString, time::Duration};
+use std::{collections::BTreeSet, convert::TryFrom, string::ToString, time::Duration};
+use tracing::warn;
 use tracing_unwrap::OptionExt;
 use tracing_unwrap::ResultExt;
 
-use crate::{domutils::*, durationutils::*, stringutils::*};
+use crate::categories::{Category, CategoryStats, Side};
+use crate::charts;
+use crate::config::Config;
+use crate::goals::Goals;
+use crate::{domutils::*, durationutils::*, game_stats::GameStats, stringutils::*};
 
 markup::define! {
     CelestePage {
@@ -41,10 +47,220 @@ markup::define! {
     }
 }
 
-pub fn celeste_stats(save: &str) -> String {
-    let mut output = String::new();
+/// Renders a Celeste `SaveData` file as a colour-coded table of per-chapter bests, how they
+/// compare to any goals, and the sum of bests of each speedrun category.
+pub struct CelesteStats {
+    pub categories: Vec<Category>,
+    pub goals: Option<Goals>,
+}
+
+impl CelesteStats {
+    pub fn new(config: &Config) -> Self {
+        CelesteStats {
+            categories: Category::all(config),
+            goals: Goals::load(config),
+        }
+    }
+}
+
+impl GameStats for CelesteStats {
+    type Model = Stats;
+
+    fn recognizes(&self, file_name: &str, contents: &str) -> bool {
+        is_save_slot(file_name, contents)
+    }
+
+    fn parse(&self, contents: &str) -> Option<Stats> {
+        let root = contents.parse::<Element>().ok()?;
+        let mut stats = Stats::from_save(&root)?;
+        stats.categories = self
+            .categories
+            .iter()
+            .map(|category| category.sum_of_bests(&stats))
+            .collect();
+        Some(stats)
+    }
+
+    fn render_html(&self, stats: &Stats) -> String {
+        let mut output = String::new();
+        output.push_str(include_str!("template.html"));
+        output.push_str(&stats_table(stats, self.goals.as_ref()));
+        output.push_str(&deaths_chart(stats));
+        output
+    }
 
-    output.push_str(include_str!("template.html"));
+    fn render_text(&self, stats: &Stats) -> String {
+        stats_table(stats, self.goals.as_ref()).strip_tags()
+    }
+
+    fn highlights(&self, before: Option<&Stats>, after: &Stats) -> Vec<String> {
+        // A first sync, or the first on a new account's branch, has nothing to compare with,
+        // and every best in the save isn't a new one.
+        let Some(before) = before else {
+            return Vec::new();
+        };
+        let mut highlights = Vec::new();
+        let assisted = if after.assisted() { " (assisted)" } else { "" };
+
+        for (key, name, chapter) in chapters(after) {
+            // A chapter that's new to the save is a first clear, which counts as a PB.
+            let previous = chapters(before)
+                .into_iter()
+                .find(|(previous, _, _)| *previous == key)
+                .map(|(_, _, previous)| previous);
+
+            let sides = [
+                (
+                    "A",
+                    &chapter.a_side.common,
+                    previous.map(|p| &p.a_side.common),
+                ),
+                (
+                    "B",
+                    &chapter.b_side.common,
+                    previous.map(|p| &p.b_side.common),
+                ),
+                (
+                    "C",
+                    &chapter.c_side.common,
+                    previous.map(|p| &p.c_side.common),
+                ),
+            ];
+            for (side, current, previous) in sides {
+                let previous = previous.and_then(|previous| previous.single_run);
+                if let Some(time) = current.single_run {
+                    if previous.map(|previous| time < previous).unwrap_or(true) {
+                        highlights.push(format!(
+                            "New PB: {name} {side}-Side in {}{assisted}",
+                            time.formatted().trim()
+                        ));
+                    }
+                }
+            }
+
+            let previous = previous.and_then(|previous| previous.a_side.full_clear);
+            if let Some(time) = chapter.a_side.full_clear {
+                if previous.map(|previous| time < previous).unwrap_or(true) {
+                    highlights.push(format!(
+                        "New PB: {name} full clear in {}{assisted}",
+                        time.formatted().trim()
+                    ));
+                }
+            }
+        }
+
+        let gained = after.total_berries.saturating_sub(before.total_berries);
+        if gained > 0 {
+            // Berries come first, since a long list of PBs may be cut short.
+            highlights.insert(0, format!("+{gained} 🍓 ({} total)", after.total_berries));
+        }
+        for level_set in &after.level_sets {
+            let previous = before
+                .level_sets
+                .iter()
+                .find(|p| p.name == level_set.name)
+                .map(|previous| previous.total_berries)
+                .unwrap_or(0);
+            let gained = level_set.total_berries.saturating_sub(previous);
+            if gained > 0 {
+                highlights.insert(
+                    0,
+                    format!(
+                        "+{gained} 🍓 in {} ({} total)",
+                        level_set.name, level_set.total_berries
+                    ),
+                );
+            }
+        }
+
+        highlights
+    }
+}
+
+/// Whether a file is one of the numbered save slots, like `0.celeste`.
+pub fn is_save_slot(file_name: &str, contents: &str) -> bool {
+    file_name.ends_with(".celeste")
+        && file_name != "settings.celeste"
+        && contents.contains("<SaveData")
+}
+
+/// A save slot's stats, if it is one and it parses.
+pub fn parse_slot(file_name: &str, contents: &str) -> Option<Stats> {
+    if !is_save_slot(file_name, contents) {
+        return None;
+    }
+    let root = contents.parse::<Element>().ok()?;
+    Stats::from_save(&root)
+}
+
+/// Every chapter's sides, vanilla and modded, keyed by the world or SID and with a display
+/// name.
+fn chapters(stats: &Stats) -> Vec<(String, String, Sides<'_>)> {
+    let vanilla = stats.worlds.iter().map(|world| {
+        let name = world.world.name().split_whitespace().join(" ");
+        (
+            format!("{:?}", world.world),
+            name,
+            Sides {
+                a_side: &world.a_side,
+                b_side: &world.b_side,
+                c_side: &world.c_side,
+            },
+        )
+    });
+    let modded = stats.level_sets.iter().flat_map(|level_set| {
+        level_set.chapters.iter().map(|chapter| {
+            (
+                chapter.sid.clone(),
+                chapter.name.clone(),
+                Sides {
+                    a_side: &chapter.a_side,
+                    b_side: &chapter.b_side,
+                    c_side: &chapter.c_side,
+                },
+            )
+        })
+    });
+    vanilla.chain(modded).collect()
+}
+
+#[derive(Clone, Copy)]
+struct Sides<'a> {
+    a_side: &'a ASideStats,
+    b_side: &'a BSideStats,
+    c_side: &'a CSideStats,
+}
+
+/// A bar chart of the deaths on each side of the vanilla chapters.
+fn deaths_chart(stats: &Stats) -> String {
+    let groups = stats
+        .worlds
+        .iter()
+        .map(|world| {
+            let label = match world.world {
+                Prologue => "P".to_string(),
+                Epilogue => "E".to_string(),
+                Core => "8".to_string(),
+                Farewell => "9".to_string(),
+                other => u32::from(other).to_string(),
+            };
+            let deaths = [
+                world.a_side.common.deaths,
+                world.b_side.common.deaths,
+                world.c_side.common.deaths,
+            ];
+            (label, deaths.into_iter().map(f64::from).collect())
+        })
+        .collect::<Vec<_>>();
+    charts::bar_chart(
+        "Deaths per chapter",
+        &groups,
+        &[("A-side", White), ("B-side", Magenta), ("C-side", Yellow)],
+    )
+}
+
+fn stats_table(stats: &Stats, goals: Option<&Goals>) -> String {
+    let mut output = String::new();
 
     output.push_str("<pre>");
 
@@ -57,6 +273,7 @@ pub fn celeste_stats(save: &str) -> String {
     const NORMAL: Color = White;
     const GOOD: Color = Magenta;
     const BEST: Color = Yellow;
+    const ASSISTED: Color = Cyan;
 
     fn print_divider(mut output: &mut String, content: impl ToString) {
         let mut s = format!("  {:<69}", content.to_string());
@@ -84,32 +301,12 @@ pub fn celeste_stats(save: &str) -> String {
         let content_len = left.len() + right.len();
 
         let mut s = String::new();
-        if content_len <= max_len || true {
-            let padding = if content_len < max_len {
-                max_len - left.len() - right.len()
-            } else {
-                0
-            };
-            s.push_str(&left);
-            for _ in 0..padding {
-                s.push(' ');
-            }
-            s.push_str(&right);
-        } else {
-            let mut left = &left[..];
-            let mut right = &right[..];
-            let mut content_len = content_len;
-            while content_len > max_len {
-                if left.is_empty() || (!right.is_empty() && content_len % 2 == 0) {
-                    right = &right[..right.len() - 1];
-                } else {
-                    left = &left[..left.len() - 1];
-                }
-                content_len -= 1;
-            }
-            s.push_str(left);
-            s.push_str(right);
+        let padding = max_len.saturating_sub(content_len);
+        s.push_str(&left);
+        for _ in 0..padding {
+            s.push(' ');
         }
+        s.push_str(&right);
 
         write!(&mut output, " {}", s.color(color)).unwrap_or_log();
         write!(&mut output, " {}", " ".background(DIVIDER)).unwrap_or_log();
@@ -124,6 +321,65 @@ pub fn celeste_stats(save: &str) -> String {
         print_cell(output, left, right, color, 19);
     }
 
+    fn print_time(
+        output: &mut String,
+        label: &str,
+        duration: Duration,
+        color: Color,
+        assisted: bool,
+    ) {
+        if assisted {
+            print_time_or_reds(output, format!("{label}*"), duration.formatted(), ASSISTED);
+        } else {
+            print_time_or_reds(output, format!("{label}:"), duration.formatted(), color);
+        }
+    }
+
+    /// A row under a best with how far it is from the target for it, if there is one.
+    fn print_goal(
+        output: &mut String,
+        goals: Option<&Goals>,
+        world: World,
+        side: Side,
+        best: Option<Duration>,
+    ) {
+        let (Some(goals), Some(best)) = (goals, best) else {
+            return;
+        };
+        let Some(target) = goals.target(world, side) else {
+            return;
+        };
+
+        let ratio = best.as_secs_f64() / target.as_secs_f64().max(0.001);
+        let color = if best <= target {
+            BEST
+        } else if ratio <= 1.05 {
+            GOOD
+        } else if ratio <= 1.25 {
+            NORMAL
+        } else {
+            SUBPAR
+        };
+        print_side(output, " ", IRRELEVANT);
+        if best <= target {
+            print_time_or_reds(output, "under:", (target - best).formatted(), color);
+        } else {
+            print_time_or_reds(output, "over:", (best - target).formatted(), color);
+        }
+        print_dashes_or_cassette(
+            output,
+            format!("{}:", goals.name),
+            target.formatted(),
+            IRRELEVANT,
+        );
+        print_deaths_or_heart(
+            output,
+            format!("{:>4.0}% of target", ratio * 100.0),
+            "",
+            color,
+        );
+    }
+
     fn print_dashes_or_cassette(
         output: &mut String,
         left: impl ToString,
@@ -143,15 +399,15 @@ pub fn celeste_stats(save: &str) -> String {
         writeln!(&mut output,).unwrap_or_log();
     }
 
-    let root = save.parse::<Element>().unwrap_or_log();
-    let stats = Stats::from_save(&root);
+    // Bests set with help are starred and coloured apart, so they can't pass for real ones.
+    let assisted = stats.assisted();
+    let mark = |color: Color| if assisted { ASSISTED } else { color };
 
     let berry_color = match stats.total_berries {
         0 => SUBPAR,
         1..=174 => NORMAL,
         175..=199 => GOOD,
-        200 => BEST,
-        _ => panic!("more than 200 berries"),
+        _ => BEST,
     };
 
     writeln!(
@@ -161,8 +417,12 @@ pub fn celeste_stats(save: &str) -> String {
         format!("{}🍓", stats.total_berries).color(berry_color)
     )
     .unwrap_or_log();
+    if let Some(badge) = stats.badge() {
+        let badge = format!(" {badge} ");
+        writeln!(&mut output, " {}", badge.color(Black).background(ASSISTED)).unwrap_or_log();
+    }
 
-    for world_stats in stats.worlds {
+    for world_stats in &stats.worlds {
         if !(world_stats.a_side.common.completed
             || world_stats.b_side.common.completed
             || world_stats.c_side.common.completed)
@@ -175,14 +435,21 @@ pub fn celeste_stats(save: &str) -> String {
         if world_stats.world == Prologue {
             let duration = world_stats.a_side.common.single_run.unwrap_or_log();
             print_side(&mut output, "p", IRRELEVANT);
-            print_time_or_reds(&mut output, "any%:", duration.formatted(), NORMAL);
+            print_time(&mut output, "any%", duration, NORMAL, assisted);
             print_dashes_or_cassette(&mut output, "can't dash", "", IRRELEVANT);
             let min_deaths = world_stats.a_side.common.fewest_deaths.unwrap_or_log();
             print_deaths_or_heart(
                 &mut output,
                 "min deaths:",
                 format!("{:>4}", min_deaths),
-                if min_deaths > 0 { NORMAL } else { BEST },
+                mark(if min_deaths > 0 { NORMAL } else { BEST }),
+            );
+            print_goal(
+                &mut output,
+                goals,
+                world_stats.world,
+                Side::A,
+                world_stats.a_side.common.single_run,
             );
             continue;
         }
@@ -194,7 +461,7 @@ pub fn celeste_stats(save: &str) -> String {
                 &mut output,
                 "min dashes:",
                 format!("{:>4}", min_dashes),
-                if min_dashes > 0 { NORMAL } else { BEST },
+                mark(if min_dashes > 0 { NORMAL } else { BEST }),
             );
             print_deaths_or_heart(&mut output, "can't die", "", IRRELEVANT);
             continue;
@@ -204,7 +471,7 @@ pub fn celeste_stats(save: &str) -> String {
             print_side(&mut output, "A", NORMAL);
 
             if let Some(duration) = world_stats.a_side.common.single_run {
-                print_time_or_reds(&mut output, "any%:", duration.formatted(), NORMAL);
+                print_time(&mut output, "any%", duration, NORMAL, assisted);
 
                 if !world_stats.has_winged_golden() {
                     let min_dashes = world_stats.a_side.common.fewest_dashes.unwrap_or_log();
@@ -212,10 +479,10 @@ pub fn celeste_stats(save: &str) -> String {
                         &mut output,
                         "min dashes:",
                         format!("{:>4}", min_dashes),
-                        if min_dashes > 0 { NORMAL } else { BEST },
+                        mark(if min_dashes > 0 { NORMAL } else { BEST }),
                     );
                 } else {
-                    print_dashes_or_cassette(&mut output, "has winged berry", "", BEST);
+                    print_dashes_or_cassette(&mut output, "has winged berry", "", mark(BEST));
                 }
 
                 if !world_stats.has_golden_a() {
@@ -224,22 +491,29 @@ pub fn celeste_stats(save: &str) -> String {
                         &mut output,
                         "min deaths:",
                         format!("{:>4}", min_deaths),
-                        if min_deaths > 0 { NORMAL } else { BEST },
+                        mark(if min_deaths > 0 { NORMAL } else { BEST }),
                     );
                 } else {
-                    print_deaths_or_heart(&mut output, "has golden berry", "", BEST);
+                    print_deaths_or_heart(&mut output, "has golden berry", "", mark(BEST));
                 }
             } else {
                 print_time_or_reds(&mut output, "segmented", "", SUBPAR);
                 print_dashes_or_cassette(&mut output, "segmented", "", SUBPAR);
                 print_deaths_or_heart(&mut output, "segmented", "", SUBPAR);
             }
+            print_goal(
+                &mut output,
+                goals,
+                world_stats.world,
+                Side::A,
+                world_stats.a_side.common.single_run,
+            );
 
             if world_stats.world.has_unlockables() {
                 print_side(&mut output, "A", NORMAL);
 
                 if let Some(duration) = world_stats.a_side.full_clear {
-                    print_time_or_reds(&mut output, "full:", duration.formatted(), BEST);
+                    print_time(&mut output, "full", duration, BEST, assisted);
                     if world_stats.world == Core {
                         print_dashes_or_cassette(
                             &mut output,
@@ -312,6 +586,13 @@ pub fn celeste_stats(save: &str) -> String {
                         print_deaths_or_heart(&mut output, "no crystal heart", "", NORMAL);
                     }
                 }
+                print_goal(
+                    &mut output,
+                    goals,
+                    world_stats.world,
+                    Side::FullClear,
+                    world_stats.a_side.full_clear,
+                );
             }
         }
 
@@ -319,14 +600,14 @@ pub fn celeste_stats(save: &str) -> String {
             print_side(&mut output, "B", GOOD);
 
             if let Some(duration) = world_stats.b_side.common.single_run {
-                print_time_or_reds(&mut output, "any%:", duration.formatted(), NORMAL);
+                print_time(&mut output, "any%", duration, NORMAL, assisted);
 
                 let min_dashes = world_stats.b_side.common.fewest_dashes.unwrap_or_log();
                 print_dashes_or_cassette(
                     &mut output,
                     "min dashes:",
                     format!("{:>4}", min_dashes),
-                    if min_dashes > 0 { NORMAL } else { BEST },
+                    mark(if min_dashes > 0 { NORMAL } else { BEST }),
                 );
 
                 if !world_stats.has_golden_b() {
@@ -335,30 +616,37 @@ pub fn celeste_stats(save: &str) -> String {
                         &mut output,
                         "min deaths:",
                         format!("{:>4}", min_deaths),
-                        if min_deaths > 0 { NORMAL } else { BEST },
+                        mark(if min_deaths > 0 { NORMAL } else { BEST }),
                     );
                 } else {
-                    print_deaths_or_heart(&mut output, "has golden berry", "", BEST);
+                    print_deaths_or_heart(&mut output, "has golden berry", "", mark(BEST));
                 }
             } else {
                 print_time_or_reds(&mut output, "segmented", "", SUBPAR);
                 print_dashes_or_cassette(&mut output, "segmented", "", SUBPAR);
                 print_deaths_or_heart(&mut output, "segmented", "", SUBPAR);
             }
+            print_goal(
+                &mut output,
+                goals,
+                world_stats.world,
+                Side::B,
+                world_stats.b_side.common.single_run,
+            );
         }
 
         if world_stats.c_side.common.completed {
             print_side(&mut output, "C", BEST);
 
             if let Some(duration) = world_stats.c_side.common.single_run {
-                print_time_or_reds(&mut output, "any%:", duration.formatted(), NORMAL);
+                print_time(&mut output, "any%", duration, NORMAL, assisted);
 
                 let min_dashes = world_stats.c_side.common.fewest_dashes.unwrap_or_log();
                 print_dashes_or_cassette(
                     &mut output,
                     "min dashes:",
                     format!("{:>4}", min_dashes),
-                    if min_dashes > 0 { NORMAL } else { BEST },
+                    mark(if min_dashes > 0 { NORMAL } else { BEST }),
                 );
 
                 if !world_stats.has_golden_c() {
@@ -367,19 +655,205 @@ pub fn celeste_stats(save: &str) -> String {
                         &mut output,
                         "min deaths:",
                         format!("{:>4}", min_deaths),
-                        if min_deaths > 0 { NORMAL } else { BEST },
+                        mark(if min_deaths > 0 { NORMAL } else { BEST }),
                     );
                 } else {
-                    print_deaths_or_heart(&mut output, "has golden berry", "", BEST);
+                    print_deaths_or_heart(&mut output, "has golden berry", "", mark(BEST));
                 }
             } else {
                 print_time_or_reds(&mut output, "segmented", "", SUBPAR);
                 print_dashes_or_cassette(&mut output, "segmented", "", SUBPAR);
                 print_deaths_or_heart(&mut output, "segmented", "", SUBPAR);
             }
+            print_goal(
+                &mut output,
+                goals,
+                world_stats.world,
+                Side::C,
+                world_stats.c_side.common.single_run,
+            );
         }
     }
 
+    for category in stats
+        .categories
+        .iter()
+        .filter(|category| category.started())
+    {
+        print_divider(
+            &mut output,
+            format!(
+                "{:<56}{:>11}",
+                category.name,
+                if category.complete() {
+                    "complete".to_string()
+                } else {
+                    format!("{}/{} done", category.legs_done, category.legs)
+                }
+            ),
+        );
+
+        print_side(&mut output, "Σ", IRRELEVANT);
+        if category.legs_done == category.legs {
+            print_time(&mut output, "sum", category.time, NORMAL, assisted);
+            match category.dashes {
+                Some(dashes) => print_dashes_or_cassette(
+                    &mut output,
+                    "min dashes:",
+                    format!("{:>4}", dashes),
+                    mark(if dashes > 0 { NORMAL } else { BEST }),
+                ),
+                None => print_dashes_or_cassette(&mut output, "full clears", "", IRRELEVANT),
+            }
+            match category.deaths {
+                Some(deaths) => print_deaths_or_heart(
+                    &mut output,
+                    "min deaths:",
+                    format!("{:>4}", deaths),
+                    mark(if deaths > 0 { NORMAL } else { BEST }),
+                ),
+                None => print_deaths_or_heart(&mut output, "full clears", "", IRRELEVANT),
+            }
+        } else {
+            print_time_or_reds(
+                &mut output,
+                format!("{:>3}/{:<3}", category.legs_done, category.legs),
+                "chapters",
+                if category.legs_done > 0 {
+                    NORMAL
+                } else {
+                    SUBPAR
+                },
+            );
+            print_dashes_or_cassette(&mut output, "incomplete", "", IRRELEVANT);
+            print_deaths_or_heart(&mut output, "incomplete", "", IRRELEVANT);
+        }
+
+        if !category.collected.is_empty() {
+            let mut cells = category
+                .collected
+                .iter()
+                .map(|progress| {
+                    (
+                        format!(
+                            "{:>3}/{:<3}",
+                            progress.have.min(progress.need),
+                            progress.need
+                        ),
+                        progress.collectible.to_string(),
+                        if progress.have >= progress.need {
+                            GOOD
+                        } else if progress.have > 0 {
+                            NORMAL
+                        } else {
+                            SUBPAR
+                        },
+                    )
+                })
+                .collect::<Vec<_>>();
+            cells.resize(3, (String::new(), String::new(), IRRELEVANT));
+            print_side(&mut output, "Σ", IRRELEVANT);
+            let (left, right, color) = &cells[0];
+            print_time_or_reds(&mut output, left, right, *color);
+            let (left, right, color) = &cells[1];
+            print_dashes_or_cassette(&mut output, left, right, *color);
+            let (left, right, color) = &cells[2];
+            print_deaths_or_heart(&mut output, left, right, *color);
+        }
+    }
+
+    for level_set in &stats.level_sets {
+        let played = level_set.chapters.iter().filter(|chapter| {
+            chapter.a_side.common.completed
+                || chapter.b_side.common.completed
+                || chapter.c_side.common.completed
+        });
+        let mut played = played.peekable();
+        if played.peek().is_none() {
+            continue;
+        }
+
+        writeln!(&mut output).unwrap_or_log();
+        writeln!(
+            &mut output,
+            " {} {}",
+            level_set.name.underline().color(White),
+            format!("{}🍓", level_set.total_berries).color(NORMAL)
+        )
+        .unwrap_or_log();
+
+        for chapter in played {
+            print_divider(&mut output, &chapter.name);
+
+            let sides = [
+                ("A", NORMAL, &chapter.a_side.common),
+                ("B", GOOD, &chapter.b_side.common),
+                ("C", BEST, &chapter.c_side.common),
+            ];
+            for (side, color, common) in sides {
+                if !common.completed {
+                    continue;
+                }
+                print_side(&mut output, side, color);
+                if let Some(duration) = common.single_run {
+                    print_time(&mut output, "any%", duration, NORMAL, assisted);
+                    let min_dashes = common.fewest_dashes.unwrap_or_default();
+                    print_dashes_or_cassette(
+                        &mut output,
+                        "min dashes:",
+                        format!("{:>4}", min_dashes),
+                        mark(if min_dashes > 0 { NORMAL } else { BEST }),
+                    );
+                    let min_deaths = common.fewest_deaths.unwrap_or_default();
+                    print_deaths_or_heart(
+                        &mut output,
+                        "min deaths:",
+                        format!("{:>4}", min_deaths),
+                        mark(if min_deaths > 0 { NORMAL } else { BEST }),
+                    );
+                } else {
+                    print_time_or_reds(&mut output, "segmented", "", SUBPAR);
+                    print_dashes_or_cassette(&mut output, "segmented", "", SUBPAR);
+                    print_deaths_or_heart(&mut output, "segmented", "", SUBPAR);
+                }
+
+                // We don't know how many berries a mod's chapter has, so only the count.
+                if side == "A" {
+                    print_side(&mut output, "A", NORMAL);
+                    match chapter.a_side.full_clear {
+                        Some(duration) => print_time(&mut output, "full", duration, BEST, assisted),
+                        None => print_time_or_reds(
+                            &mut output,
+                            format!("{:>3}", common.berry_count()),
+                            "berries",
+                            NORMAL,
+                        ),
+                    }
+                    if chapter.a_side.cassette {
+                        print_dashes_or_cassette(&mut output, "has cassette", "", GOOD);
+                    } else {
+                        print_dashes_or_cassette(&mut output, "no cassette", "", NORMAL);
+                    }
+                    if chapter.a_side.heart {
+                        print_deaths_or_heart(&mut output, "has crystal heart", "", GOOD);
+                    } else {
+                        print_deaths_or_heart(&mut output, "no crystal heart", "", NORMAL);
+                    }
+                }
+            }
+        }
+    }
+
+    if assisted {
+        writeln!(&mut output).unwrap_or_log();
+        writeln!(
+            &mut output,
+            " {}",
+            "* may have been set with assists, variants or cheats".color(ASSISTED)
+        )
+        .unwrap_or_log();
+    }
+
     output.push_str("</pre>");
 
     output
@@ -395,6 +869,137 @@ pub struct Stats {
     pub worlds: Vec<WorldStats>,
     pub total_berries: u32,
     pub gems: u8,
+    /// Level sets from mods loaded by Everest, other than the vanilla one.
+    #[serde(default)]
+    pub level_sets: Vec<LevelSetStats>,
+    /// The Assist Mode and Variant Mode options, as last set. The game keeps them even while
+    /// neither mode is on.
+    #[serde(default)]
+    pub assists: Assists,
+    /// The sum of bests of each speedrun category, filled in by [`CelesteStats`].
+    #[serde(default)]
+    pub categories: Vec<CategoryStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Assists {
+    /// In tenths of normal speed, so 7 is 70%.
+    pub game_speed: u32,
+    pub invincible: bool,
+    /// `Normal`, `Two` or `Infinite` air dashes.
+    pub dash_mode: String,
+    pub dash_assist: bool,
+    pub infinite_stamina: bool,
+    pub mirror_mode: bool,
+    pub three_sixty_dashing: bool,
+    pub invisible_motion: bool,
+    pub no_grabbing: bool,
+    pub low_friction: bool,
+    pub super_dashing: bool,
+    pub hiccups: bool,
+    pub play_as_badeline: bool,
+}
+
+impl std::default::Default for Assists {
+    fn default() -> Self {
+        Assists {
+            game_speed: 10,
+            invincible: false,
+            dash_mode: "Normal".to_string(),
+            dash_assist: false,
+            infinite_stamina: false,
+            mirror_mode: false,
+            three_sixty_dashing: false,
+            invisible_motion: false,
+            no_grabbing: false,
+            low_friction: false,
+            super_dashing: false,
+            hiccups: false,
+            play_as_badeline: false,
+        }
+    }
+}
+
+impl Assists {
+    /// Reads an `Assists` element, leaving anything missing or unreadable at its default.
+    pub fn from_save(assists: &minidom::Element) -> Self {
+        let mut parsed = Assists::default();
+        for el in assists.children() {
+            let text = el.text();
+            let text = text.trim();
+            let flag = text == "true";
+            match el.name() {
+                "GameSpeed" => parsed.game_speed = text.parse().unwrap_or(parsed.game_speed),
+                "Invincible" => parsed.invincible = flag,
+                "DashMode" => parsed.dash_mode = text.to_string(),
+                "DashAssist" => parsed.dash_assist = flag,
+                "InfiniteStamina" => parsed.infinite_stamina = flag,
+                "MirrorMode" => parsed.mirror_mode = flag,
+                "ThreeSixtyDashing" => parsed.three_sixty_dashing = flag,
+                "InvisibleMotion" => parsed.invisible_motion = flag,
+                "NoGrabbing" => parsed.no_grabbing = flag,
+                "LowFriction" => parsed.low_friction = flag,
+                "SuperDashing" => parsed.super_dashing = flag,
+                "Hiccups" => parsed.hiccups = flag,
+                "PlayAsBadeline" => parsed.play_as_badeline = flag,
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    /// Every option that differs from normal play, like `70% speed` or `invincible`.
+    pub fn active(&self) -> Vec<String> {
+        let mut active = Vec::new();
+        if self.game_speed != 10 {
+            active.push(format!("{}% speed", self.game_speed * 10));
+        }
+        match self.dash_mode.as_str() {
+            "Normal" => {}
+            "Two" => active.push("two air dashes".to_string()),
+            "Infinite" => active.push("infinite air dashes".to_string()),
+            other => active.push(format!("{other} air dashes")),
+        }
+        let flags = [
+            (self.invincible, "invincible"),
+            (self.infinite_stamina, "infinite stamina"),
+            (self.dash_assist, "dash assist"),
+            (self.mirror_mode, "mirror mode"),
+            (self.three_sixty_dashing, "360° dashing"),
+            (self.invisible_motion, "invisible motion"),
+            (self.no_grabbing, "no grabbing"),
+            (self.low_friction, "low friction"),
+            (self.super_dashing, "super dashing"),
+            (self.hiccups, "hiccups"),
+            (self.play_as_badeline, "playing as Badeline"),
+        ];
+        active.extend(
+            flags
+                .into_iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, name)| name.to_string()),
+        );
+        active
+    }
+}
+
+/// A mod's campaign, as Everest records it, named like `SpringCollab2020/1-Beginner`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelSetStats {
+    pub name: String,
+    pub total_berries: u32,
+    pub chapters: Vec<ChapterStats>,
+}
+
+/// A chapter from a mod, which we know only by its SID, like
+/// `SpringCollab2020/1-Beginner/Cellular`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterStats {
+    pub sid: String,
+    pub name: String,
+    pub a_side: ASideStats,
+    pub b_side: BSideStats,
+    pub c_side: CSideStats,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -406,22 +1011,37 @@ pub struct WorldStats {
 }
 
 impl WorldStats {
+    /// The red berries collected, leaving out the goldens the save lists among them, and
+    /// anything past the chapter's total that a modded or edited save might claim.
     pub fn red_berries(&self) -> u32 {
-        let actual = self.a_side.common.berry_count();
-        let max = self.world.red_berries();
-        if actual <= max {
-            actual
-        } else if actual == max + 1 {
-            max
-        } else if self.world == ForsakenCity && actual == max + 2 {
-            max
-        } else {
-            panic!("impossibly large number of berries")
-        }
+        let reds = self
+            .a_side
+            .common
+            .berries
+            .iter()
+            .filter(|key| !self.is_golden(key))
+            .count() as u32;
+        reds.min(self.world.red_berries())
+    }
+
+    /// Whether an A-side berry's key is for a golden: one in the chapter's first room or, in
+    /// Forsaken City, the winged golden in its last.
+    fn is_golden(&self, key: &str) -> bool {
+        self.is_first_golden(key) || (self.world == ForsakenCity && room_of(key) == WINGED_ROOM)
+    }
+
+    fn is_first_golden(&self, key: &str) -> bool {
+        self.world
+            .golden_room()
+            .is_none_or(|room| room_of(key) == room)
     }
 
     pub fn has_golden_a(&self) -> bool {
-        self.a_side.common.berry_count() > self.world.red_berries()
+        self.a_side
+            .common
+            .berries
+            .iter()
+            .any(|key| self.is_first_golden(key))
     }
 
     pub fn has_golden_b(&self) -> bool {
@@ -434,16 +1054,44 @@ impl WorldStats {
 
     pub fn has_winged_golden(&self) -> bool {
         self.world == ForsakenCity
-            && self.a_side.common.berry_count() > self.world.red_berries() + 1
+            && self
+                .a_side
+                .common
+                .berries
+                .iter()
+                .any(|key| room_of(key) == WINGED_ROOM)
+    }
+
+    /// Farewell's moon berry, which is the only berry there besides the golden.
+    pub fn has_moon_berry(&self) -> bool {
+        self.world == Farewell
+            && self
+                .a_side
+                .common
+                .berries
+                .iter()
+                .any(|key| !self.is_first_golden(key))
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The room Forsaken City's winged golden appears in, at the memorial, for those who got there
+/// without dashing.
+const WINGED_ROOM: &str = "end";
+
+/// The room of a berry's key, which the save writes as `room:id`.
+fn room_of(key: &str) -> &str {
+    key.rsplit_once(':').map_or(key, |(room, _)| room)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SideStatsCommon {
     pub completed: bool,
     pub single_run: Option<Duration>,
     pub fewest_dashes: Option<u32>,
     pub fewest_deaths: Option<u32>,
+    /// Every death on this side, across all attempts.
+    #[serde(default)]
+    pub deaths: u32,
     pub berries: BTreeSet<String>,
 }
 
@@ -453,7 +1101,7 @@ impl SideStatsCommon {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ASideStats {
     pub cassette: bool,
     pub heart: bool,
@@ -461,17 +1109,17 @@ pub struct ASideStats {
     pub common: SideStatsCommon,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BSideStats {
     pub common: SideStatsCommon,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CSideStats {
     pub common: SideStatsCommon,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Copy)]
 pub enum World {
     Prologue,
     ForsakenCity,
@@ -484,7 +1132,6 @@ pub enum World {
     Epilogue,
     Core,
     Farewell,
-    SumOfBests,
 }
 
 pub use self::World::*;
@@ -503,14 +1150,28 @@ impl World {
             Epilogue => "    Epilogue",
             Core => "8.  Core",
             Farewell => "9.  Farewell",
-            SumOfBests => "    Sum of Bests",
         }
     }
 
     pub fn has_unlockables(self) -> bool {
+        !matches!(self, Prologue | Epilogue)
+    }
+
+    /// The room an A-side starts in, where its golden berry waits. The save records berries
+    /// by their key, `room:id`, and no red berry is in a chapter's first room, so the room is
+    /// enough to tell the golden apart, whatever the entity IDs in a given version of the map.
+    /// Sides without red berries or a moon berry need no room: any berry there is the golden.
+    pub fn golden_room(self) -> Option<&'static str> {
         match self {
-            Prologue | Epilogue => false,
-            _ => true,
+            Prologue | Reflection | Epilogue => None,
+            ForsakenCity => Some("1"),
+            OldSite => Some("start"),
+            CelestialResort => Some("s0"),
+            GoldenRidge => Some("a-00"),
+            MirrorTemple => Some("a-00b"),
+            TheSummit => Some("a-00"),
+            Core => Some("a-00"),
+            Farewell => Some("intro-00-past"),
         }
     }
 
@@ -525,7 +1186,6 @@ impl World {
             TheSummit => 47,
             Core => 5,
             Farewell => 0,
-            SumOfBests => 20 + 18 + 25 + 29 + 31 + 47 + 5,
         }
     }
 }
@@ -536,9 +1196,11 @@ impl std::fmt::Display for World {
     }
 }
 
-impl From<u32> for World {
-    fn from(id: u32) -> Self {
-        match id {
+impl TryFrom<u32> for World {
+    type Error = u32;
+
+    fn try_from(id: u32) -> Result<Self, u32> {
+        Ok(match id {
             0 => Prologue,
             1 => ForsakenCity,
             2 => OldSite,
@@ -550,9 +1212,8 @@ impl From<u32> for World {
             8 => Epilogue,
             9 => Core,
             10 => Farewell,
-            100 => SumOfBests,
-            _ => panic!("unknown world ID"),
-        }
+            other => return Err(other),
+        })
     }
 }
 
@@ -570,257 +1231,167 @@ impl From<World> for u32 {
             Epilogue => 8,
             Core => 9,
             Farewell => 10,
-            SumOfBests => 100,
         }
     }
 }
 
 impl Stats {
-    pub fn from_save(save_data: &minidom::Element) -> Self {
-        assert!(save_data.name() == "SaveData");
+    /// Whether any of the slot's times may have been set with help, which keeps it out of
+    /// comparisons and leaderboards unless `saves.includeAssisted` is set.
+    pub fn assisted(&self) -> bool {
+        self.cheat_mode || self.assist_mode || self.variant_mode
+    }
 
-        let version = save_data.expect_child("Version").text();
+    /// What was on, like `Assist Mode: 70% speed, invincible`, or nothing for a normal slot.
+    pub fn badge(&self) -> Option<String> {
+        let modes = [
+            (self.assist_mode, "Assist Mode"),
+            (self.variant_mode, "Variant Mode"),
+            (self.cheat_mode, "Cheat Mode"),
+        ]
+        .into_iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| name)
+        .join(" + ");
+        if modes.is_empty() {
+            return None;
+        }
+        // The options are only in effect in Assist Mode or Variant Mode.
+        let active = if self.assist_mode || self.variant_mode {
+            self.assists.active()
+        } else {
+            Vec::new()
+        };
+        Some(if active.is_empty() {
+            modes
+        } else {
+            format!("{modes}: {}", active.join(", "))
+        })
+    }
+
+    /// Reads a `SaveData` element, or gives up on one missing what every save has.
+    pub fn from_save(save_data: &minidom::Element) -> Option<Self> {
+        if save_data.name() != "SaveData" {
+            return None;
+        }
 
-        let name = save_data.expect_child("Name").text();
+        let version = save_data.only_child("Version")?.text();
+
+        let name = save_data.only_child("Name")?.text();
 
         let gem_el = save_data.children().find(|el| el.name() == "SummitGems");
 
         let gems = match gem_el {
             Some(el) => u8::try_from(el.children().filter(|el| el.text() == "true").count())
-                .expect("way too many gems"),
+                .unwrap_or(u8::MAX),
             None => 0,
         };
 
-        let cheat_mode = save_data.expect_parse_child("CheatMode");
-        let assist_mode = save_data.expect_parse_child("AssistMode");
-        let variant_mode = save_data.expect_parse_child("VariantMode");
+        let cheat_mode = save_data.parse_child("CheatMode")?;
+        let assist_mode = save_data.parse_child("AssistMode")?;
+        let variant_mode = save_data.parse_child("VariantMode")?;
 
-        let total_berries = save_data.expect_parse_child("TotalStrawberries");
+        let assists = save_data
+            .children()
+            .find(|el| el.name() == "Assists")
+            .map(Assists::from_save)
+            .unwrap_or_default();
+
+        let total_berries = save_data.parse_child("TotalStrawberries")?;
+
+        let mut worlds = Vec::new();
+        let mut level_sets = Vec::<LevelSetStats>::new();
+        // A mod's chapter that doesn't parse is only that mod's problem, so it's left out
+        // rather than taking the rest of the slot with it.
+        let mut add_chapter = |level_set: Option<&str>, area_stats: &minidom::Element| {
+            let Some(chapter) = ChapterStats::from_save(area_stats) else {
+                warn!(
+                    "Skipping chapter {:?}, which doesn't parse",
+                    area_stats.attr("SID").unwrap_or_default()
+                );
+                return;
+            };
+            let level_set = match level_set {
+                Some(level_set) => level_set.to_string(),
+                None => level_set_of(&chapter.sid),
+            };
+            let index = match level_sets.iter().position(|set| set.name == level_set) {
+                Some(index) => index,
+                None => {
+                    level_sets.push(LevelSetStats {
+                        name: level_set.to_string(),
+                        total_berries: 0,
+                        chapters: Vec::new(),
+                    });
+                    level_sets.len() - 1
+                }
+            };
+            let level_set = &mut level_sets[index];
+            if !level_set.chapters.iter().any(|c| c.sid == chapter.sid) {
+                level_set.chapters.push(chapter);
+            }
+        };
 
-        let mut worlds: Vec<_> = save_data
-            .expect_child("Areas")
+        // Everest adds an SID to every area. Areas from mods may turn up among the vanilla
+        // ones, where their numeric IDs depend on which mods happen to be loaded.
+        for area_stats in save_data.only_child("Areas")?.children() {
+            let sid = area_stats.attr("SID");
+            let vanilla = sid
+                .map(|sid| level_set_of(sid) == "Celeste")
+                .unwrap_or(true);
+            let world = area_stats
+                .attr("ID")
+                .and_then(|id| id.parse::<u32>().ok())
+                .and_then(|id| World::try_from(id).ok());
+            match (vanilla, world) {
+                (true, Some(world)) => {
+                    let stats = WorldStats::from_save(world, area_stats)?;
+                    if stats.world != Epilogue {
+                        worlds.push(stats);
+                    }
+                }
+                _ => add_chapter(None, area_stats),
+            }
+        }
+
+        for level_set in save_data
             .children()
-            .map(WorldStats::from_save)
-            .filter(|stats| stats.world != Epilogue)
-            .collect();
+            .filter(|el| el.name() == "LevelSets")
+            .flat_map(|el| el.children())
+        {
+            let Some(name) = level_set.attr("Name") else {
+                continue;
+            };
+            if name == "Celeste" {
+                continue;
+            }
+            for area_stats in level_set
+                .children()
+                .filter(|el| el.name() == "Areas")
+                .flat_map(|el| el.children())
+            {
+                add_chapter(Some(name), area_stats);
+            }
+        }
 
-        worlds.push(WorldStats {
-            world: SumOfBests,
-            a_side: ASideStats {
-                cassette: worlds
-                    .iter()
-                    .filter(|world_stats| world_stats.world.has_unlockables())
-                    .all(|world_stats| world_stats.a_side.cassette),
-                full_clear: if worlds
+        for level_set in &mut level_sets {
+            let recorded = save_data
+                .children()
+                .filter(|el| el.name() == "LevelSets")
+                .flat_map(|el| el.children())
+                .find(|el| el.attr("Name") == Some(level_set.name.as_str()))
+                .and_then(|el| el.children().find(|el| el.name() == "TotalStrawberries"))
+                .and_then(|el| el.text().parse().ok());
+            level_set.total_berries = recorded.unwrap_or_else(|| {
+                level_set
+                    .chapters
                     .iter()
-                    .filter(|world_stats| world_stats.world.has_unlockables())
-                    .all(|world_stats| world_stats.a_side.full_clear.is_some())
-                {
-                    Some(
-                        worlds
-                            .iter()
-                            .filter(|world_stats| world_stats.world.has_unlockables())
-                            .map(|world_stats| world_stats.a_side.full_clear.unwrap_or_log())
-                            .sum(),
-                    )
-                } else {
-                    None
-                },
-                heart: worlds.iter().all(|world_stats| world_stats.a_side.heart),
-                common: SideStatsCommon {
-                    completed: worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.a_side.common.completed),
-                    berries: worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .flat_map(|world_stats| {
-                            (0..world_stats.red_berries()).map(|n| n.to_string()).map(
-                                move |mut s| {
-                                    s.push(':');
-                                    s.push_str(world_stats.world.name());
-                                    s
-                                },
-                            )
-                        })
-                        .collect(),
-                    fewest_dashes: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.a_side.common.fewest_dashes.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.a_side.common.fewest_dashes.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                    fewest_deaths: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.a_side.common.fewest_deaths.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.a_side.common.fewest_deaths.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                    single_run: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.a_side.common.single_run.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.a_side.common.single_run.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                },
-            },
-            b_side: BSideStats {
-                common: SideStatsCommon {
-                    completed: worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.b_side.common.completed),
-                    berries: BTreeSet::new(),
-                    fewest_dashes: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.b_side.common.fewest_dashes.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.b_side.common.fewest_dashes.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                    fewest_deaths: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.b_side.common.fewest_deaths.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.b_side.common.fewest_deaths.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                    single_run: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.b_side.common.single_run.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.b_side.common.single_run.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                },
-            },
-            c_side: CSideStats {
-                common: SideStatsCommon {
-                    completed: worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.c_side.common.completed),
-                    berries: BTreeSet::new(),
-                    fewest_dashes: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.c_side.common.fewest_dashes.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.c_side.common.fewest_dashes.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                    fewest_deaths: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.c_side.common.fewest_deaths.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.c_side.common.fewest_deaths.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                    single_run: if worlds
-                        .iter()
-                        .filter(|world_stats| world_stats.world.has_unlockables())
-                        .all(|world_stats| world_stats.c_side.common.single_run.is_some())
-                    {
-                        Some(
-                            worlds
-                                .iter()
-                                .filter(|world_stats| world_stats.world.has_unlockables())
-                                .map(|world_stats| {
-                                    world_stats.c_side.common.single_run.unwrap_or_log()
-                                })
-                                .sum(),
-                        )
-                    } else {
-                        None
-                    },
-                },
-            },
-        });
+                    .map(|chapter| chapter.a_side.common.berry_count())
+                    .sum()
+            });
+        }
 
-        Self {
+        Some(Self {
             version,
             name,
             gems,
@@ -829,61 +1400,130 @@ impl Stats {
             variant_mode,
             total_berries,
             worlds,
+            level_sets,
+            assists,
+            categories: Vec::new(),
+        })
+    }
+}
+
+/// The level set an SID belongs to: everything before its last `/`.
+fn level_set_of(sid: &str) -> String {
+    match sid.rsplit_once('/') {
+        Some((level_set, _)) => level_set.to_string(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// A readable chapter name from the last part of an SID, so `1-ForsakenCity` becomes
+/// `1. Forsaken City`.
+fn chapter_name(sid: &str) -> String {
+    let file = sid.rsplit('/').next().unwrap_or(sid);
+    let (number, title) = match file.split_once('-') {
+        Some((number, title)) if number.chars().all(|c| c.is_ascii_digit()) => {
+            (Some(number), title)
         }
+        _ => (None, file),
+    };
+
+    let mut name = String::new();
+    let mut previous: Option<char> = None;
+    for c in title.chars() {
+        let c = match (c, previous) {
+            ('_', _) => ' ',
+            (c, None) => c.to_ascii_uppercase(),
+            (c, _) => c,
+        };
+        if let Some(previous) = previous {
+            if c.is_uppercase() && previous.is_lowercase() {
+                name.push(' ');
+            }
+        }
+        name.push(c);
+        previous = Some(c);
+    }
+
+    match number {
+        Some(number) => format!("{number}. {name}"),
+        None => name,
     }
 }
 
 impl WorldStats {
-    pub fn from_save(area_stats: &minidom::Element) -> Self {
-        assert!(area_stats.name() == "AreaStats");
+    pub fn from_save(world: World, area_stats: &minidom::Element) -> Option<Self> {
+        let (a_side, b_side, c_side) = sides_from_save(area_stats)?;
+        Some(Self {
+            world,
+            a_side,
+            b_side,
+            c_side,
+        })
+    }
+}
 
-        let world = area_stats.expect_parse_attr::<u32>("ID").into();
+impl ChapterStats {
+    pub fn from_save(area_stats: &minidom::Element) -> Option<Self> {
+        let sid = match area_stats.attr("SID") {
+            Some(sid) => sid.to_string(),
+            None => format!("Unknown/{}", area_stats.attr("ID").unwrap_or("?")),
+        };
+        let (a_side, b_side, c_side) = sides_from_save(area_stats)?;
+        Some(Self {
+            name: chapter_name(&sid),
+            sid,
+            a_side,
+            b_side,
+            c_side,
+        })
+    }
+}
 
-        let modes = area_stats
-            .expect_child("Modes")
-            .children()
-            .collect::<Vec<_>>();
-        assert!(modes.len() == 3);
+/// Reads an `AreaStats` element's sides. Modded chapters may have fewer than three, and are
+/// given empty stats for the ones they don't have.
+fn sides_from_save(area_stats: &minidom::Element) -> Option<(ASideStats, BSideStats, CSideStats)> {
+    if area_stats.name() != "AreaStats" {
+        return None;
+    }
 
-        let sides_common = modes
-            .iter()
-            .map(|area_mode_stats| SideStatsCommon::from_save(area_mode_stats))
-            .collect::<Vec<_>>();
+    let modes = area_stats
+        .only_child("Modes")?
+        .children()
+        .collect::<Vec<_>>();
+
+    let side = |index: usize| match modes.get(index) {
+        Some(area_mode_stats) => SideStatsCommon::from_save(area_mode_stats),
+        None => Some(SideStatsCommon::default()),
+    };
 
-        let a_side = ASideStats {
-            cassette: area_stats.expect_parse_attr::<bool>("Cassette"),
-            heart: modes[0].expect_parse_attr::<bool>("HeartGem"),
+    let a_side = match modes.first() {
+        Some(a_mode) => ASideStats {
+            cassette: area_stats.parse_attr::<bool>("Cassette")?,
+            heart: a_mode.parse_attr::<bool>("HeartGem")?,
             full_clear: {
-                let decimicroseconds: u64 = modes[0].expect_parse_attr("BestFullClearTime");
+                let decimicroseconds: u64 = a_mode.parse_attr("BestFullClearTime")?;
                 if decimicroseconds == 0 {
                     None
                 } else {
                     Some(Duration::from_nanos(decimicroseconds * 100))
                 }
             },
-            common: sides_common[0].clone(),
-        };
-        let b_side = BSideStats {
-            common: sides_common[1].clone(),
-        };
-        let c_side = CSideStats {
-            common: sides_common[2].clone(),
-        };
+            common: side(0)?,
+        },
+        None => ASideStats::default(),
+    };
+    let b_side = BSideStats { common: side(1)? };
+    let c_side = CSideStats { common: side(2)? };
 
-        Self {
-            world,
-            a_side,
-            b_side,
-            c_side,
-        }
-    }
+    Some((a_side, b_side, c_side))
 }
 
 impl SideStatsCommon {
-    pub fn from_save(area_mode_stats: &minidom::Element) -> Self {
-        assert!(area_mode_stats.name() == "AreaModeStats");
+    pub fn from_save(area_mode_stats: &minidom::Element) -> Option<Self> {
+        if area_mode_stats.name() != "AreaModeStats" {
+            return None;
+        }
 
-        let completed = area_mode_stats.expect_parse_attr("Completed");
+        let completed = area_mode_stats.parse_attr("Completed")?;
 
         let single_run;
         let fewest_dashes;
@@ -893,29 +1533,162 @@ impl SideStatsCommon {
         let single_run_completed = single_run_completed_attr == Some("true");
         if single_run_completed {
             single_run = {
-                let decimicroseconds: u64 = area_mode_stats.expect_parse_attr("BestTime");
+                let decimicroseconds: u64 = area_mode_stats.parse_attr("BestTime")?;
                 Some(Duration::from_nanos(decimicroseconds * 100))
             };
-            fewest_dashes = Some(area_mode_stats.expect_parse_attr("BestDashes"));
-            fewest_deaths = Some(area_mode_stats.expect_parse_attr("BestDeaths"));
+            fewest_dashes = Some(area_mode_stats.parse_attr("BestDashes")?);
+            fewest_deaths = Some(area_mode_stats.parse_attr("BestDeaths")?);
         } else {
             single_run = None;
             fewest_dashes = None;
             fewest_deaths = None;
         };
 
+        let deaths = area_mode_stats
+            .attr("Deaths")
+            .and_then(|deaths| deaths.parse().ok())
+            .unwrap_or_default();
+
         let berries = area_mode_stats
-            .expect_child("Strawberries")
+            .only_child("Strawberries")?
             .children()
-            .map(|entity_id| entity_id.attr("Key").unwrap_or_log().to_string())
+            .filter_map(|entity_id| Some(entity_id.attr("Key")?.to_string()))
             .collect::<BTreeSet<_>>();
 
-        Self {
+        Some(Self {
             completed,
             single_run,
             fewest_dashes,
             fewest_deaths,
+            deaths,
             berries,
-        }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils;
+
+    /// A save with Forsaken City's A-Side beaten in `best_time` (in ticks of 100ns) and
+    /// `berries` strawberries collected.
+    fn save_xml(best_time: u64, berries: u32) -> String {
+        let keys = (0..berries)
+            .map(|index| format!("r{index}:{index}"))
+            .collect::<Vec<_>>();
+        let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+        testutils::save_xml("Madeline", &[(1, best_time, &keys)])
+    }
+
+    fn save(best_time: u64, berries: u32) -> Stats {
+        CelesteStats::new(&Config::default())
+            .parse(&save_xml(best_time, berries))
+            .unwrap()
+    }
+
+    #[test]
+    fn malformed_saves_dont_parse() {
+        let renderer = CelesteStats::new(&Config::default());
+        assert!(renderer.parse("<SaveData").is_none());
+        assert!(renderer
+            .parse(r#"<SaveData xmlns=""><Name>Madeline</Name></SaveData>"#)
+            .is_none());
+        let missing_time = save_xml(1_000_000_000, 5).replace(r#"BestTime="1000000000""#, "");
+        assert!(renderer.parse(&missing_time).is_none());
+        assert!(crate::game_stats::SaveRenderer::render(&renderer, &missing_time).is_none());
+    }
+
+    #[test]
+    fn red_berries_stop_at_the_chapter_total() {
+        let stats = save(1_000_000_000, 25);
+        assert_eq!(stats.worlds[0].red_berries(), 20);
+    }
+
+    #[test]
+    fn first_sync_has_no_highlights() {
+        let renderer = CelesteStats::new(&Config::default());
+        assert_eq!(
+            renderer.highlights(None, &save(1_000_000_000, 5)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn highlights_new_berries_and_pbs() {
+        let renderer = CelesteStats::new(&Config::default());
+        let before = save(1_000_000_000, 5);
+        assert_eq!(
+            renderer.highlights(Some(&before), &before),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            renderer.highlights(Some(&before), &save(900_000_000, 7)),
+            [
+                "+2 🍓 (7 total)",
+                "New PB: 1. Forsaken City A-Side in 1m30.000s"
+            ]
+        );
+    }
+
+    /// A mod's chapter with the given SID, and its A-side's best time in ticks of 100ns.
+    fn mod_area(sid: &str, best_time: u64) -> String {
+        format!(
+            r#"<AreaStats ID="11" SID="{sid}" Cassette="false"><Modes><AreaModeStats Completed="true" SingleRunCompleted="true" BestTime="{best_time}" BestFullClearTime="0" BestDashes="0" BestDeaths="1" HeartGem="true"><Strawberries /></AreaModeStats></Modes></AreaStats>"#
+        )
+    }
+
+    #[test]
+    fn groups_mod_chapters_by_level_set() {
+        let areas = [
+            // Mods' chapters can take IDs that would be vanilla ones.
+            mod_area("SpringCollab2020/1-Beginner/Flowers", 1_000_000_000)
+                .replace(r#"ID="11""#, r#"ID="2""#),
+            mod_area("SpringCollab2020/1-Beginner/Flowers", 1_000_000_000),
+            mod_area("SpringCollab2020/1-Beginner/Broken", 1_000_000_000)
+                .replace("<Modes>", "")
+                .replace("</Modes>", ""),
+        ]
+        .concat();
+        let level_set = format!(
+            r#"<LevelSets><LevelSetStats Name="Other/Set"><Areas>{}</Areas><TotalStrawberries>3</TotalStrawberries></LevelSetStats></LevelSets>"#,
+            mod_area("Other/Set/2-Deep_Dive", 500_000_000)
+        );
+        let xml = save_xml(1_000_000_000, 5)
+            .replace("</Areas>", &format!("{areas}</Areas>"))
+            .replace("</SaveData>", &format!("{level_set}</SaveData>"));
+        let stats = CelesteStats::new(&Config::default()).parse(&xml).unwrap();
+
+        assert_eq!(stats.worlds.len(), 1);
+        let level_sets = stats
+            .level_sets
+            .iter()
+            .map(|level_set| {
+                let chapters = level_set
+                    .chapters
+                    .iter()
+                    .map(|chapter| chapter.name.as_str())
+                    .collect::<Vec<_>>();
+                (level_set.name.as_str(), level_set.total_berries, chapters)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            level_sets,
+            [
+                ("SpringCollab2020/1-Beginner", 0, vec!["Flowers"]),
+                ("Other/Set", 3, vec!["2. Deep Dive"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_chapters_from_sids() {
+        assert_eq!(chapter_name("Celeste/1-ForsakenCity"), "1. Forsaken City");
+        assert_eq!(chapter_name("Mod/Set/lost_woods"), "Lost woods");
+        assert_eq!(chapter_name("Mod/Set/10-TheEnd"), "10. The End");
+        assert_eq!(chapter_name("Mod/Set/Pre-Game"), "Pre-Game");
+        assert_eq!(chapter_name("NoSlash"), "No Slash");
+        assert_eq!(level_set_of("Mod/Set/Chapter"), "Mod/Set");
+        assert_eq!(level_set_of("NoSlash"), "Unknown");
     }
 }
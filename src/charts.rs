@@ -0,0 +1,217 @@
+//! Inline SVG charts, drawn by hand so the pages need no scripts or other files and render the
+//! same on GitHub Pages as from disk.
+
+use itertools::Itertools;
+use std::fmt::Write;
+use tracing_unwrap::ResultExt;
+
+use crate::durationutils::date;
+use crate::stringutils::*;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 240.0;
+/// Room for the title above the plot, and for labels to its left and below it.
+const TOP: f64 = 32.0;
+const LEFT: f64 = 88.0;
+const RIGHT: f64 = 16.0;
+const BOTTOM: f64 = 40.0;
+
+const AXIS: Color = DarkGray;
+const LABEL: Color = LightGray;
+
+/// A line on a [`line_chart`], through points of a Unix time and a value.
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub name: String,
+    pub color: Color,
+    pub points: Vec<(i64, f64)>,
+}
+
+/// Bars in groups, like a chapter's sides, with a legend naming the bars of each group.
+pub fn bar_chart(title: &str, groups: &[(String, Vec<f64>)], legend: &[(&str, Color)]) -> String {
+    let max = groups
+        .iter()
+        .flat_map(|(_, values)| values.iter().copied())
+        .fold(0.0, f64::max);
+    let mut svg = start(title, max, |value| format!("{value:.0}"));
+
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let group_width = plot_width / groups.len().max(1) as f64;
+    for (index, (label, values)) in groups.iter().enumerate() {
+        let x = LEFT + group_width * index as f64;
+        let bar_width = (group_width - 8.0) / values.len().max(1) as f64;
+        for (bar, value) in values.iter().enumerate() {
+            let height = y_scale(*value, max);
+            let color = legend.get(bar).map_or(White, |(_, color)| *color);
+            writeln!(
+                svg,
+                r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"><title>{} {}: {value}</title></rect>"#,
+                x + 4.0 + bar_width * bar as f64,
+                HEIGHT - BOTTOM - height,
+                bar_width,
+                height,
+                color.css_color(),
+                escape(label),
+                legend.get(bar).map_or("", |(name, _)| name),
+            )
+            .unwrap_or_log();
+        }
+        text(
+            &mut svg,
+            x + group_width / 2.0,
+            HEIGHT - BOTTOM + 14.0,
+            "middle",
+            LABEL,
+            label,
+        );
+    }
+
+    write_legend(&mut svg, legend);
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Lines over time, with the value axis labelled by `format`.
+pub fn line_chart(title: &str, series: &[Series], format: impl Fn(f64) -> String) -> String {
+    let points = series.iter().flat_map(|series| series.points.iter());
+    let max = points.clone().map(|(_, value)| *value).fold(0.0, f64::max);
+    let first = points
+        .clone()
+        .map(|(time, _)| *time)
+        .min()
+        .unwrap_or_default();
+    let last = points.map(|(time, _)| *time).max().unwrap_or_default();
+    let mut svg = start(title, max, format);
+
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let x = |time: i64| {
+        if last > first {
+            LEFT + plot_width * (time - first) as f64 / (last - first) as f64
+        } else {
+            LEFT + plot_width / 2.0
+        }
+    };
+    for series in series {
+        // Commits aren't always in time order, like after a rebase.
+        let points = series
+            .points
+            .iter()
+            .sorted_by_key(|(time, _)| *time)
+            .map(|(time, value)| {
+                format!(
+                    "{:.1},{:.1}",
+                    x(*time),
+                    HEIGHT - BOTTOM - y_scale(*value, max)
+                )
+            })
+            .collect::<Vec<_>>();
+        writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="2"><title>{}</title></polyline>"#,
+            points.join(" "),
+            series.color.css_color(),
+            escape(&series.name)
+        )
+        .unwrap_or_log();
+        // A single point makes no line, so each point gets a dot too.
+        for point in &points {
+            let (cx, cy) = point.split_once(',').unwrap_or_default();
+            writeln!(
+                svg,
+                r#"<circle cx="{cx}" cy="{cy}" r="2.5" fill="{}" />"#,
+                series.color.css_color()
+            )
+            .unwrap_or_log();
+        }
+    }
+
+    text(
+        &mut svg,
+        LEFT,
+        HEIGHT - BOTTOM + 14.0,
+        "start",
+        LABEL,
+        &date(first),
+    );
+    if last > first {
+        text(
+            &mut svg,
+            WIDTH - RIGHT,
+            HEIGHT - BOTTOM + 14.0,
+            "end",
+            LABEL,
+            &date(last),
+        );
+    }
+
+    let legend = series
+        .iter()
+        .map(|series| (series.name.as_str(), series.color))
+        .collect::<Vec<_>>();
+    write_legend(&mut svg, &legend);
+    svg.push_str("</svg>");
+    svg
+}
+
+/// The opening tag, title, axes and value labels shared by every chart.
+fn start(title: &str, max: f64, format: impl Fn(f64) -> String) -> String {
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="monospace" font-size="12" style="margin: 2em; vertical-align: top; background: black; box-shadow: 0 0 8px black, 0 0 4px #888;">"#
+    )
+    .unwrap_or_log();
+    text(&mut svg, LEFT, 20.0, "start", White, title);
+
+    let bottom = HEIGHT - BOTTOM;
+    writeln!(
+        svg,
+        r#"<path d="M{LEFT},{TOP} V{bottom} H{}" fill="none" stroke="{}" />"#,
+        WIDTH - RIGHT,
+        AXIS.css_color()
+    )
+    .unwrap_or_log();
+    text(&mut svg, LEFT - 6.0, bottom, "end", LABEL, &format(0.0));
+    if max > 0.0 {
+        text(&mut svg, LEFT - 6.0, TOP + 4.0, "end", LABEL, &format(max));
+    }
+    svg
+}
+
+/// Scales a value to a height within the plot.
+fn y_scale(value: f64, max: f64) -> f64 {
+    if max > 0.0 {
+        (HEIGHT - TOP - BOTTOM) * value / max
+    } else {
+        0.0
+    }
+}
+
+fn write_legend(svg: &mut String, legend: &[(&str, Color)]) {
+    let mut x = LEFT;
+    for (name, color) in legend {
+        writeln!(
+            svg,
+            r#"<rect x="{x:.1}" y="{:.1}" width="8" height="8" fill="{}" />"#,
+            HEIGHT - 16.0,
+            color.css_color()
+        )
+        .unwrap_or_log();
+        text(svg, x + 12.0, HEIGHT - 8.0, "start", LABEL, name);
+        x += 12.0 + 7.5 * name.len() as f64 + 12.0;
+    }
+}
+
+fn text(svg: &mut String, x: f64, y: f64, anchor: &str, color: Color, content: &str) {
+    writeln!(
+        svg,
+        r#"<text x="{x:.1}" y="{y:.1}" text-anchor="{anchor}" fill="{}">{}</text>"#,
+        color.css_color(),
+        escape(content)
+    )
+    .unwrap_or_log();
+}
+
+fn escape(text: &str) -> String {
+    html_escape::encode_text(text).into_owned()
+}
@@ -0,0 +1,333 @@
+//! Several save slots side by side, with each chapter side's bests in one row, the best of each
+//! row highlighted, and a count of how many bests each slot holds.
+
+use minidom::Element;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+use tracing_unwrap::ResultExt;
+
+use crate::categories::{Best, Leg, Side, CHAPTERS};
+use crate::celeste_stats::{Stats, World::*};
+use crate::config::Config;
+use crate::durationutils::*;
+use crate::stringutils::*;
+
+pub const COMPARE_FILE: &str = "compare.html";
+
+/// One of the saves being compared.
+#[derive(Debug, Clone)]
+pub struct Entrant {
+    /// Who the save belongs to, like `Madeline (0.celeste)`.
+    pub label: String,
+    pub stats: Stats,
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub entrants: Vec<Entrant>,
+    /// The labels of saves left out for being played with assists, variants or cheats.
+    pub excluded: Vec<String>,
+}
+
+/// One leg's bests across the entrants, in the same order.
+struct Row {
+    leg: Leg,
+    bests: Vec<Option<Best>>,
+}
+
+impl Comparison {
+    /// Leaves out assisted saves unless the config's `saves.includeAssisted` lets them in.
+    pub fn new(entrants: Vec<Entrant>, config: &Config) -> Self {
+        let (entrants, excluded): (Vec<_>, Vec<_>) = entrants
+            .into_iter()
+            .partition(|entrant| config.include_assisted || !entrant.stats.assisted());
+        Comparison {
+            entrants,
+            excluded: excluded.into_iter().map(|entrant| entrant.label).collect(),
+        }
+    }
+
+    /// Every chapter side with a recorded best, in chapter order.
+    pub fn legs() -> Vec<Leg> {
+        let mut legs = vec![Leg {
+            world: Prologue,
+            side: Side::A,
+        }];
+        for world in CHAPTERS {
+            for side in [Side::A, Side::FullClear, Side::B, Side::C] {
+                legs.push(Leg { world, side });
+            }
+        }
+        legs.push(Leg {
+            world: Farewell,
+            side: Side::A,
+        });
+        legs
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        Comparison::legs()
+            .into_iter()
+            .map(|leg| Row {
+                leg,
+                bests: self
+                    .entrants
+                    .iter()
+                    .map(|entrant| leg.best(&entrant.stats))
+                    .collect(),
+            })
+            .filter(|row| row.bests.iter().any(Option::is_some))
+            .collect()
+    }
+
+    /// How many of the rows' best times, dashes and deaths each entrant holds, ties included.
+    pub fn bests_held(&self) -> Vec<u32> {
+        let mut held = vec![0; self.entrants.len()];
+        for row in self.rows() {
+            for (index, best) in row.bests.iter().enumerate() {
+                let Some(best) = best else {
+                    continue;
+                };
+                held[index] += u32::from(Some(best.time) == row.best_time())
+                    + u32::from(best.dashes.is_some() && best.dashes == row.fewest_dashes())
+                    + u32::from(best.deaths.is_some() && best.deaths == row.fewest_deaths());
+            }
+        }
+        held
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut output = String::new();
+        output.push_str(include_str!("template.html"));
+        output.push_str(&self.table());
+        output
+    }
+
+    pub fn render_text(&self) -> String {
+        self.table().strip_tags()
+    }
+
+    fn table(&self) -> String {
+        const HEADER_FG: Color = Black;
+        const HEADER_BG: Color = White;
+        const NORMAL: Color = White;
+        const BEST: Color = Yellow;
+        const MISSING: Color = DarkGray;
+        const ASSISTED: Color = Cyan;
+
+        let mut output = String::new();
+        output.push_str("<pre>");
+
+        let mut header = format!("  {:<32}", "");
+        for entrant in &self.entrants {
+            write!(header, " {:>27}", truncate(&entrant.label, 27)).unwrap_or_log();
+        }
+        writeln!(
+            output,
+            "{}",
+            html_escape::encode_text(&format!("{header} "))
+                .to_string()
+                .color(HEADER_FG)
+                .background(HEADER_BG)
+        )
+        .unwrap_or_log();
+        let mut units = format!("  {:<32}", "");
+        for _ in &self.entrants {
+            write!(units, " {:>13} {:>6} {:>6}", "time", "dashes", "deaths").unwrap_or_log();
+        }
+        writeln!(output, "{}", units.color(MISSING)).unwrap_or_log();
+
+        let cell = |value: Option<String>, best: bool, width: usize| match value {
+            Some(value) => format!("{value:>width$}").color(if best { BEST } else { NORMAL }),
+            None => format!("{:>width$}", "-").color(MISSING),
+        };
+        for row in self.rows() {
+            write!(
+                output,
+                "  {:<4} {:<27}",
+                row.leg.code(),
+                truncate(&row.leg.name(), 27)
+            )
+            .unwrap_or_log();
+            for best in &row.bests {
+                let time = best.map(|best| best.time);
+                let dashes = best.and_then(|best| best.dashes);
+                let deaths = best.and_then(|best| best.deaths);
+                write!(
+                    output,
+                    " {} {} {}",
+                    cell(
+                        time.map(|time| time.formatted()),
+                        time.is_some() && time == row.best_time(),
+                        13
+                    ),
+                    cell(
+                        dashes.map(|dashes| dashes.to_string()),
+                        dashes.is_some() && dashes == row.fewest_dashes(),
+                        6
+                    ),
+                    cell(
+                        deaths.map(|deaths| deaths.to_string()),
+                        deaths.is_some() && deaths == row.fewest_deaths(),
+                        6
+                    ),
+                )
+                .unwrap_or_log();
+            }
+            output.push('\n');
+        }
+
+        write!(output, "\n  {:<32}", "bests held").unwrap_or_log();
+        let held = self.bests_held();
+        let most = held.iter().copied().max().unwrap_or_default();
+        for held in held {
+            let color = if held == most && held > 0 {
+                BEST
+            } else {
+                NORMAL
+            };
+            write!(output, " {}", format!("{held:>27}").color(color)).unwrap_or_log();
+        }
+        output.push('\n');
+
+        for label in &self.excluded {
+            writeln!(
+                output,
+                "  {}",
+                html_escape::encode_text(&format!(
+                    "{label} was left out for using assists, variants or cheats"
+                ))
+                .to_string()
+                .color(ASSISTED)
+            )
+            .unwrap_or_log();
+        }
+        output.push_str("</pre>");
+        output
+    }
+}
+
+impl Row {
+    fn best_time(&self) -> Option<Duration> {
+        self.bests.iter().flatten().map(|best| best.time).min()
+    }
+
+    fn fewest_dashes(&self) -> Option<u32> {
+        self.bests
+            .iter()
+            .flatten()
+            .filter_map(|best| best.dashes)
+            .min()
+    }
+
+    fn fewest_deaths(&self) -> Option<u32> {
+        self.bests
+            .iter()
+            .flatten()
+            .filter_map(|best| best.deaths)
+            .min()
+    }
+}
+
+/// Shortens text to fit a column, with an ellipsis if anything was cut.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        text.chars().take(width - 1).chain(['…']).collect()
+    }
+}
+
+/// `compare SAVE SAVE...` prints a comparison of several save slots.
+pub fn compare_command(args: &[&str]) {
+    if args.len() < 2 {
+        eprintln!("Usage: compare SAVE SAVE...");
+        std::process::exit(2);
+    }
+
+    let entrants = args
+        .iter()
+        .map(|path| {
+            let path = Path::new(path);
+            let contents = std::fs::read_to_string(path).unwrap_or_else(|err| {
+                eprintln!("Can't read {path:?}: {err}");
+                std::process::exit(2);
+            });
+            let Some(stats) = contents
+                .parse::<Element>()
+                .ok()
+                .and_then(|root| Stats::from_save(&root))
+            else {
+                eprintln!("{path:?} isn't a save slot");
+                std::process::exit(2);
+            };
+            let file = path.file_name().unwrap_or(path.as_os_str());
+            Entrant {
+                label: label(&stats, &file.to_string_lossy()),
+                stats,
+            }
+        })
+        .collect();
+    let comparison = Comparison::new(entrants, &Config::current());
+    print!("{}", comparison.render_text());
+}
+
+/// Names a save by its player and file, like `Madeline (0.celeste)`.
+pub fn label(stats: &Stats, file: &str) -> String {
+    format!("{} ({file})", stats.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils;
+
+    fn entrant(name: &str, areas: &[(u32, u64, &[&str])]) -> Entrant {
+        let xml = testutils::save_xml(name, areas);
+        Entrant {
+            label: name.to_string(),
+            stats: Stats::from_save(&xml.parse::<Element>().unwrap()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn counts_bests_held_with_ties() {
+        // Every run in these saves has 3 dashes and 4 deaths, except where replaced.
+        let madeline = entrant("Madeline", &[(1, 900_000_000, &[]), (2, 600_000_000, &[])]);
+        let mut badeline = entrant("Badeline", &[(1, 900_000_000, &[])]);
+        badeline.stats.worlds[0].a_side.common.fewest_dashes = Some(1);
+        let theo = entrant("Theo", &[(1, 1_000_000_000, &[])]);
+
+        let comparison = Comparison::new(vec![madeline, badeline, theo], &Config::default());
+        // 1A: Madeline and Badeline tie on time, and everyone ties on deaths. 2A is
+        // Madeline's alone.
+        assert_eq!(comparison.bests_held(), [5, 3, 1]);
+    }
+
+    #[test]
+    fn leaves_out_assisted_saves() {
+        let mut assisted = entrant("Granny", &[(1, 100_000_000, &[])]);
+        assisted.stats.assist_mode = true;
+        let comparison = Comparison::new(
+            vec![
+                entrant("Madeline", &[(1, 900_000_000, &[])]),
+                assisted.clone(),
+            ],
+            &Config::default(),
+        );
+        assert_eq!(comparison.excluded, ["Granny"]);
+        assert_eq!(comparison.bests_held(), [3]);
+
+        let config = Config {
+            include_assisted: true,
+            ..Config::default()
+        };
+        let comparison = Comparison::new(
+            vec![entrant("Madeline", &[(1, 900_000_000, &[])]), assisted],
+            &config,
+        );
+        assert!(comparison.excluded.is_empty());
+        assert_eq!(comparison.bests_held(), [2, 3]);
+    }
+}
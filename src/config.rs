@@ -0,0 +1,236 @@
+use git2::Repository;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::dirs::GIT_DIR;
+
+/// User settings, read from the `[saves]` section of the sync repo's git config, e.g.
+/// `git --git-dir ~/.celeste-saves/git config saves.cloudReport false`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Include the Steam Cloud sync report in each commit message.
+    pub cloud_report: bool,
+    /// How long to wait for the game to start after asking Steam to launch it, once Steam is
+    /// running (`saves.launchTimeout`, in seconds).
+    pub launch_timeout: Duration,
+    /// The longest to wait for save files and Steam Cloud to settle after the game exits
+    /// (`saves.settleTimeout`, in seconds).
+    pub settle_timeout: Duration,
+    /// Show a desktop notification summarising each sync (`saves.notify`).
+    pub notify: bool,
+    /// How many days of log files to keep (`saves.logRetention`).
+    pub log_retention: usize,
+    /// `text` or `json` (`saves.logFormat`).
+    pub log_format: LogFormat,
+    /// Let slots played with assists, variants or cheats into comparisons and leaderboards
+    /// (`saves.includeAssisted`).
+    pub include_assisted: bool,
+    /// Extra speedrun categories, each like `Early Game: 1A 2A 3A` (`saves.category`, which
+    /// may be given more than once). See [`crate::categories::Category::parse`].
+    pub categories: Vec<String>,
+    /// A TOML or JSON file of target times to compare bests against (`saves.goals`). If unset,
+    /// `goals.toml` or `goals.json` in the data directory is used if it exists.
+    pub goals: Option<PathBuf>,
+    /// Friends whose saves to fetch for the leaderboard, each set up with
+    /// `saves.<remote>.url`, `saves.<remote>.name` and `saves.<remote>.branch`.
+    pub friends: Vec<Friend>,
+}
+
+/// Someone else's sync repo, whose branches are fetched into `refs/remotes/<remote>/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Friend {
+    /// A short name for the remote, used in its refs, like `alice`.
+    pub remote: String,
+    /// How the leaderboard shows them, like `Alice` (`saves.<remote>.name`). Defaults to the
+    /// remote's name.
+    pub name: String,
+    /// Anything `git fetch` takes, including a path to a local bare repo.
+    pub url: String,
+    /// The one branch to fetch (`saves.<remote>.branch`). If unset, every branch starting
+    /// with `celeste` is, which covers the `celeste` branch as well as the
+    /// `celeste-<account id>` ones for each Steam account.
+    pub branch: Option<String>,
+}
+
+impl Friend {
+    /// The branches to fetch, as a pattern `git fetch` and [`git2::Repository::references_glob`]
+    /// both take.
+    pub fn branches(&self) -> &str {
+        self.branch.as_deref().unwrap_or("celeste*")
+    }
+
+    /// Where the fetched branches are kept.
+    pub fn remote_refs(&self) -> String {
+        format!("refs/remotes/{}/{}", self.remote, self.branches())
+    }
+
+    pub fn refspec(&self) -> String {
+        format!("+refs/heads/{}:{}", self.branches(), self.remote_refs())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, with the spans each event happened in.
+    Json,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cloud_report: true,
+            launch_timeout: Duration::from_secs(24),
+            settle_timeout: Duration::from_secs(32),
+            notify: true,
+            log_retention: 14,
+            log_format: LogFormat::Text,
+            include_assisted: false,
+            categories: Vec::new(),
+            goals: None,
+            friends: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(repo: &Repository) -> Self {
+        let defaults = Config::default();
+        let Ok(config) = repo.config() else {
+            return defaults;
+        };
+
+        Config {
+            cloud_report: config
+                .get_bool("saves.cloudReport")
+                .unwrap_or(defaults.cloud_report),
+            launch_timeout: get_seconds(&config, "saves.launchTimeout")
+                .unwrap_or(defaults.launch_timeout),
+            settle_timeout: get_seconds(&config, "saves.settleTimeout")
+                .unwrap_or(defaults.settle_timeout),
+            notify: config.get_bool("saves.notify").unwrap_or(defaults.notify),
+            log_retention: config
+                .get_i64("saves.logRetention")
+                .map(|days| days.max(1) as usize)
+                .unwrap_or(defaults.log_retention),
+            log_format: match config.get_string("saves.logFormat").as_deref() {
+                Ok("json") => LogFormat::Json,
+                Ok("text") => LogFormat::Text,
+                _ => defaults.log_format,
+            },
+            include_assisted: config
+                .get_bool("saves.includeAssisted")
+                .unwrap_or(defaults.include_assisted),
+            categories: get_multivar(&config, "saves.category"),
+            goals: config.get_path("saves.goals").ok(),
+            friends: get_friends(&config),
+        }
+    }
+
+    /// The config of the sync repo, or the defaults if it hasn't been created yet.
+    pub fn current() -> Self {
+        Repository::open_bare(&*GIT_DIR)
+            .map(|repo| Config::load(&repo))
+            .unwrap_or_default()
+    }
+}
+
+fn get_seconds(config: &git2::Config, key: &str) -> Option<Duration> {
+    let seconds = config.get_i64(key).ok()?;
+    Some(Duration::from_secs(seconds.max(0) as u64))
+}
+
+fn get_multivar(config: &git2::Config, key: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    if let Ok(entries) = config.multivar(key, None) {
+        let _ = entries.for_each(|entry| values.extend(entry.value().map(str::to_string)));
+    }
+    values
+}
+
+/// Whether any config key starts with `prefix`.
+fn has_entries(config: &git2::Config, prefix: &str) -> bool {
+    let mut found = false;
+    if let Ok(entries) = config.entries(None) {
+        let _ = entries.for_each(|entry| {
+            found |= entry.name().is_some_and(|name| name.starts_with(prefix));
+        });
+    }
+    found
+}
+
+/// Every `saves.<remote>.url`, with its `saves.<remote>.name` and `saves.<remote>.branch`.
+/// Remotes and branches that wouldn't make a valid ref, and remotes named like one of the repo's
+/// own, are logged and skipped.
+fn get_friends(config: &git2::Config) -> Vec<Friend> {
+    let mut urls = Vec::new();
+    if let Ok(entries) = config.entries(Some(r"^saves\..+\.url$")) {
+        let _ = entries.for_each(|entry| {
+            let (Some(name), Some(url)) = (entry.name(), entry.value()) else {
+                return;
+            };
+            let remote = &name["saves.".len()..name.len() - ".url".len()];
+            urls.push((remote.to_string(), url.to_string()));
+        });
+    }
+
+    let mut friends = Vec::new();
+    for (remote, url) in urls {
+        if !git2::Reference::is_valid_name(&format!("refs/remotes/{remote}/celeste")) {
+            warn!("Ignoring saves.{remote}.url, since {remote:?} can't be used as a remote name");
+            continue;
+        }
+        // A friend's branches are fetched, with pruning, into `refs/remotes/<remote>/`, which
+        // would wipe out the tracking branches of a real remote by that name.
+        if remote == "origin" || has_entries(config, &format!("remote.{remote}.")) {
+            warn!("Ignoring saves.{remote}.url, since there's already a remote named {remote:?}");
+            continue;
+        }
+        let name = config
+            .get_string(&format!("saves.{remote}.name"))
+            .unwrap_or_else(|_| remote.clone());
+        let branch = config.get_string(&format!("saves.{remote}.branch")).ok();
+        if let Some(branch) = &branch {
+            if !git2::Reference::is_valid_name(&format!("refs/heads/{branch}")) {
+                warn!("Ignoring saves.{remote}.url, since saves.{remote}.branch {branch:?} isn't a valid branch name");
+                continue;
+            }
+        }
+        friends.push(Friend {
+            remote,
+            name,
+            url,
+            branch,
+        });
+    }
+    friends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::scratch_dir;
+
+    #[test]
+    fn friends_cant_reuse_remote_names() {
+        let repo = Repository::init_bare(scratch_dir("config-friends")).unwrap();
+        let mut config = repo.config().unwrap();
+        config
+            .set_str("remote.backup.url", "/srv/saves.git")
+            .unwrap();
+        for remote in ["origin", "backup", "theo", "bad name"] {
+            config
+                .set_str(&format!("saves.{remote}.url"), "/srv/friend.git")
+                .unwrap();
+        }
+        let friends = get_friends(&repo.config().unwrap().snapshot().unwrap());
+        assert_eq!(
+            friends
+                .iter()
+                .map(|friend| friend.remote.as_str())
+                .collect::<Vec<_>>(),
+            ["theo"]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! A Unix socket the syncing process serves, so the otherwise invisible daemon can be asked
+//! what it's doing, told to sync mid-session, or stopped.
+
+use once_cell::sync::Lazy;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::Shutdown;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tracing::debug;
+use tracing::info;
+use tracing::instrument;
+use tracing::warn;
+use tracing_unwrap::ResultExt;
+
+use crate::dirs::RUNTIME_DIR;
+use crate::lock;
+use crate::lock::LockInfo;
+use crate::steam_app::CELESTE;
+use crate::sync::SyncSession;
+use crate::NAME;
+
+pub static SOCKET_PATH: Lazy<PathBuf> = Lazy::new(|| RUNTIME_DIR.join(format!("{NAME}.sock")));
+
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// A handle on the listener being served, so serving again or closing can stop its thread.
+static LISTENER: Mutex<Option<UnixListener>> = Mutex::new(None);
+
+/// Bumped whenever a listener is stopped, so its thread knows the failed accept that follows
+/// means it should exit.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The session in progress, if any, for `sync-now` and `stop` to act on.
+static SESSION: Mutex<Option<Arc<Mutex<SyncSession>>>> = Mutex::new(None);
+
+pub fn set_session(session: Option<Arc<Mutex<SyncSession>>>) {
+    *SESSION.lock().unwrap_or_log() = session;
+}
+
+fn session() -> Option<Arc<Mutex<SyncSession>>> {
+    SESSION.lock().unwrap_or_log().clone()
+}
+
+/// Whether a `stop` request has cancelled this session's sync.
+pub fn stopped() -> bool {
+    STOPPED.load(Ordering::SeqCst)
+}
+
+/// Serves control requests on a background thread, taking the socket over from any previous
+/// server, including an earlier one of ours, whose thread exits. If `exit_on_stop` is set a
+/// `stop` request exits the process; otherwise it only cancels the sync, for when we're
+/// wrapping the game and must keep waiting for it.
+#[instrument]
+pub fn serve(exit_on_stop: bool) {
+    stop_serving();
+    std::fs::remove_file(&*SOCKET_PATH).ok();
+    let listener = match UnixListener::bind(&*SOCKET_PATH) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("Not serving control socket {:?}: {err}", &*SOCKET_PATH);
+            return;
+        }
+    };
+    debug!("Serving control socket {:?}", &*SOCKET_PATH);
+
+    let generation = GENERATION.load(Ordering::SeqCst);
+    *LISTENER.lock().unwrap_or_log() = listener.try_clone().ok();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => handle(stream, exit_on_stop),
+                Err(_) if GENERATION.load(Ordering::SeqCst) != generation => break,
+                Err(err) => debug!("Control socket accept failed: {err}"),
+            }
+        }
+        debug!("Stopped serving control socket");
+    });
+}
+
+/// Wakes our listener's thread, if there is one, and lets it exit. The socket file may well
+/// belong to another process by now, so it's left alone.
+fn stop_serving() {
+    let Some(listener) = LISTENER.lock().unwrap_or_log().take() else {
+        return;
+    };
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    // Shutting down a listening socket makes any blocked accept on it fail.
+    // SAFETY: `listener` owns the fd and stays alive until after the call, so the fd can't
+    // have been closed or reused for something else; shutdown(2) touches no memory of ours.
+    unsafe {
+        libc::shutdown(listener.as_raw_fd(), libc::SHUT_RDWR);
+    }
+}
+
+/// Whether anyone is answering on the socket.
+pub fn serving() -> bool {
+    UnixStream::connect(&*SOCKET_PATH).is_ok()
+}
+
+fn handle(mut stream: UnixStream, exit_on_stop: bool) {
+    let mut command = String::new();
+    BufReader::new(&stream).read_line(&mut command).ok();
+    let command = command.trim();
+    // `serving` connects without sending anything.
+    if command.is_empty() {
+        return;
+    }
+    info!("Control request: {command:?}");
+
+    let mut respond = |message: String| {
+        stream.write_all(message.as_bytes()).ok();
+        stream.write_all(b"\n").ok();
+    };
+
+    match command {
+        "status" => respond(status()),
+        "sync-now" => {
+            let Some(session) = session() else {
+                respond("No session in progress.".to_string());
+                return;
+            };
+            let phase = lock::phase();
+            respond("Syncing...".to_string());
+            let commit = session.lock().unwrap_or_log().finish();
+            if let Some(phase) = phase {
+                lock::set_phase(phase);
+            }
+            respond(match commit {
+                Some(commit) => format!("Committed {commit}."),
+                None => "No changes to save.".to_string(),
+            });
+        }
+        "stop" => {
+            // Waiting for the session means we never stop partway through a commit.
+            let session = session();
+            let _session = session
+                .as_ref()
+                .map(|session| session.lock().unwrap_or_log());
+            STOPPED.store(true, Ordering::SeqCst);
+            close();
+            lock::release();
+            if exit_on_stop {
+                respond("Stopped.".to_string());
+                stream.shutdown(Shutdown::Both).ok();
+                info!("Stopping at control request.");
+                exit(0);
+            }
+            respond("Stopped; this session won't be synced.".to_string());
+        }
+        other => respond(format!("Unknown command {other:?}")),
+    }
+}
+
+fn status() -> String {
+    let pid = std::process::id();
+    match lock::info() {
+        Some(info) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let running = Duration::from_secs(now.saturating_sub(info.since));
+            format!("PID {pid}: {} (running for {running:?})", info.phase)
+        }
+        None => format!("PID {pid}: idle, waiting for {} to start", CELESTE.name),
+    }
+}
+
+/// Stops serving and removes the socket, so clients know nobody is serving it.
+pub fn close() {
+    stop_serving();
+    std::fs::remove_file(&*SOCKET_PATH).ok();
+}
+
+/// Sends `command` to the running daemon and prints its response. Returns whether a daemon
+/// answered.
+pub fn request(command: &str) -> bool {
+    let Ok(mut stream) = UnixStream::connect(&*SOCKET_PATH) else {
+        match LockInfo::live() {
+            Some(holder) => println!(
+                "{NAME} is running as PID {} ({}), but isn't serving {:?}.",
+                holder.pid, holder.phase, &*SOCKET_PATH
+            ),
+            None => println!("{NAME} isn't running."),
+        }
+        return false;
+    };
+    stream
+        .write_all(format!("{command}\n").as_bytes())
+        .unwrap_or_log();
+    stream.shutdown(Shutdown::Write).ok();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok();
+    print!("{response}");
+    true
+}
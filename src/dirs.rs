@@ -18,3 +18,16 @@ pub static GIT_DIR: Lazy<PathBuf> = Lazy::new(|| DATA_DIR.join("git"));
 pub static STEAM_DIR: Lazy<PathBuf> = Lazy::new(|| HOME_DIR.join(".local/share/Steam"));
 
 pub static STEAM_USER_DATA_DIR: Lazy<PathBuf> = Lazy::new(|| STEAM_DIR.join("userdata"));
+
+pub static SYSTEMD_USER_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| HOME_DIR.join(".config"))
+        .join("systemd/user")
+});
+
+pub static RUNTIME_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| DATA_DIR.clone())
+});
@@ -1,30 +1,26 @@
 use minidom::Element;
-use tracing_unwrap::OptionExt;
 
+/// Lookups for reading saves, which give up with `None` rather than panicking on a file that
+/// isn't shaped as expected.
 pub trait DomUtils {
-    fn expect_parse_attr<T: std::str::FromStr>(&self, name: &str) -> T;
-    fn expect_child(&self, name: &str) -> &Self;
-    fn expect_parse_child<T: std::str::FromStr>(&self, name: &str) -> T;
+    fn parse_attr<T: std::str::FromStr>(&self, name: &str) -> Option<T>;
+    /// The child with the given name, if there's exactly one.
+    fn only_child(&self, name: &str) -> Option<&Self>;
+    fn parse_child<T: std::str::FromStr>(&self, name: &str) -> Option<T>;
 }
 
 impl DomUtils for Element {
-    fn expect_parse_attr<T: std::str::FromStr>(&self, name: &str) -> T {
-        self.attr(name)
-            .expect(name)
-            .parse::<T>()
-            .ok()
-            .unwrap_or_log()
+    fn parse_attr<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.attr(name)?.parse::<T>().ok()
     }
 
-    fn expect_child(&self, name: &str) -> &Self {
-        let matches: Vec<&Self> = self.children().filter(|el| el.name() == name).collect();
-        assert!(matches.len() == 1);
-        matches[0]
+    fn only_child(&self, name: &str) -> Option<&Self> {
+        let mut matches = self.children().filter(|el| el.name() == name);
+        let only = matches.next()?;
+        matches.next().is_none().then_some(only)
     }
 
-    fn expect_parse_child<T: std::str::FromStr>(&self, name: &str) -> T {
-        let matches: Vec<&Self> = self.children().filter(|el| el.name() == name).collect();
-        assert!(matches.len() == 1);
-        matches[0].text().parse::<T>().ok().unwrap_or_log()
+    fn parse_child<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.only_child(name)?.text().parse::<T>().ok()
     }
 }
@@ -1,5 +1,8 @@
 pub trait DurationUtils {
     fn formatted(&self) -> String;
+    /// As LiveSplit writes times, like `00:01:38.2400000`, in ticks of 100ns, which is also
+    /// what Celeste counts in.
+    fn livesplit(&self) -> String;
 }
 
 impl DurationUtils for std::time::Duration {
@@ -40,4 +43,108 @@ impl DurationUtils for std::time::Duration {
 
         format!("{:>13}", pieces)
     }
+
+    fn livesplit(&self) -> String {
+        let seconds = self.as_secs();
+        format!(
+            "{:02}:{:02}:{:02}.{:07}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            self.subsec_nanos() / 100
+        )
+    }
+}
+
+/// Parses a time as people write them, like `1:38.240`, `1:02:03`, `45.5` or our own
+/// `2m49.647s`, or as LiveSplit does, like `00:01:38.2400000`.
+pub fn parse_duration(text: &str) -> Option<std::time::Duration> {
+    let text = text.trim().trim_end_matches('s').replace(['h', 'm'], ":");
+    if text.split(':').count() > 3 {
+        return None;
+    }
+    let mut parts = text.split(':').rev();
+
+    let seconds = parts.next()?;
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{fraction:0<9}").parse::<u32>().ok()?;
+
+    let mut seconds = whole.trim().parse::<u64>().ok()?;
+    for (part, unit) in parts.zip([60, 60 * 60]) {
+        seconds += part.trim().parse::<u64>().ok()? * unit;
+    }
+
+    Some(std::time::Duration::new(seconds, nanos))
+}
+
+/// A UTC date like `2023-04-01`, from seconds since the Unix epoch.
+pub fn date(unix_seconds: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm.
+    let days = unix_seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_durations() {
+        let time = Some(Duration::from_millis(98_240));
+        assert_eq!(parse_duration("1:38.240"), time);
+        assert_eq!(parse_duration("1m38.240s"), time);
+        assert_eq!(parse_duration(" 00:01:38.2400000 "), time);
+        assert_eq!(parse_duration("98.24"), time);
+        assert_eq!(
+            parse_duration("2m49.647s"),
+            Some(Duration::from_millis(169_647))
+        );
+        assert_eq!(parse_duration("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("1h02m03s"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("45.5"), Some(Duration::from_millis(45_500)));
+    }
+
+    #[test]
+    fn rejects_other_text() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("fast"), None);
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("1.-5"), None);
+        assert_eq!(parse_duration("1.0000000001"), None);
+    }
+
+    #[test]
+    fn formats_for_livesplit() {
+        assert_eq!(Duration::ZERO.livesplit(), "00:00:00.0000000");
+        assert_eq!(
+            Duration::from_millis(98_240).livesplit(),
+            "00:01:38.2400000"
+        );
+        let long = Duration::new(3723, 123_456_700);
+        assert_eq!(long.livesplit(), "01:02:03.1234567");
+        assert_eq!(parse_duration(&long.livesplit()), Some(long));
+    }
+
+    #[test]
+    fn formats_for_tables() {
+        assert_eq!(Duration::from_millis(98_240).formatted(), "    1m38.240s");
+        assert_eq!(Duration::new(3723, 0).formatted(), " 1h02m03.000s");
+    }
 }
@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::path::Path;
+use tracing::warn;
+use tracing_unwrap::ResultExt;
+
+use crate::celeste_settings::CelesteSettings;
+use crate::celeste_stats::CelesteStats;
+use crate::config::Config;
+use crate::stringutils::StringUtils;
+
+/// Recognizes, parses and renders the save files of one game (or one view of a game).
+pub trait GameStats {
+    /// The parsed, serializable representation of a save file.
+    type Model: Serialize;
+
+    /// Whether this renderer handles the save file with the given name and contents.
+    fn recognizes(&self, file_name: &str, contents: &str) -> bool;
+
+    /// The save's model, or `None` if it isn't shaped the way this renderer expects, so that
+    /// it can be committed as-is rather than stopping the sync.
+    fn parse(&self, contents: &str) -> Option<Self::Model>;
+
+    fn render_html(&self, model: &Self::Model) -> String;
+
+    fn render_text(&self, model: &Self::Model) -> String {
+        self.render_html(model).strip_tags()
+    }
+
+    fn render_json(&self, model: &Self::Model) -> String {
+        serde_json::to_string_pretty(model).unwrap_or_log()
+    }
+
+    /// Notable progress since an earlier version of the save, such as new personal bests, as
+    /// short lines for a notification.
+    fn highlights(&self, _before: Option<&Self::Model>, _after: &Self::Model) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A save file rendered in every supported format.
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub html: String,
+    pub text: String,
+    pub json: String,
+}
+
+/// Object-safe wrapper around [`GameStats`], so renderers with different models can be
+/// dispatched on at runtime.
+pub trait SaveRenderer {
+    fn recognizes(&self, file_name: &str, contents: &str) -> bool;
+    fn render(&self, contents: &str) -> Option<Rendered>;
+    fn highlights(&self, before: Option<&str>, after: &str) -> Vec<String>;
+}
+
+impl<T: GameStats> SaveRenderer for T {
+    fn recognizes(&self, file_name: &str, contents: &str) -> bool {
+        GameStats::recognizes(self, file_name, contents)
+    }
+
+    fn render(&self, contents: &str) -> Option<Rendered> {
+        let model = self.parse(contents)?;
+        Some(Rendered {
+            html: self.render_html(&model),
+            text: self.render_text(&model),
+            json: self.render_json(&model),
+        })
+    }
+
+    fn highlights(&self, before: Option<&str>, after: &str) -> Vec<String> {
+        let Some(after) = self.parse(after) else {
+            return Vec::new();
+        };
+        // An earlier version that doesn't parse is as good as none.
+        let before = before.and_then(|before| self.parse(before));
+        GameStats::highlights(self, before.as_ref(), &after)
+    }
+}
+
+pub fn renderers(config: &Config) -> Vec<Box<dyn SaveRenderer>> {
+    vec![
+        Box::new(CelesteStats::new(config)),
+        Box::new(CelesteSettings),
+    ]
+}
+
+/// Renders a save file with the first renderer that recognizes it, if any, and if it parses.
+pub fn render(config: &Config, file_name: &str, contents: &str) -> Option<Rendered> {
+    let renderer = renderers(config)
+        .into_iter()
+        .find(|renderer| renderer.recognizes(file_name, contents))?;
+    let rendered = renderer.render(contents);
+    if rendered.is_none() {
+        warn!("Couldn't parse {file_name:?}");
+    }
+    rendered
+}
+
+/// Notable progress between two versions of a save file, if any renderer recognizes it.
+pub fn highlights(
+    config: &Config,
+    file_name: &str,
+    before: Option<&str>,
+    after: &str,
+) -> Vec<String> {
+    renderers(config)
+        .into_iter()
+        .find(|renderer| renderer.recognizes(file_name, after))
+        .map(|renderer| renderer.highlights(before, after))
+        .unwrap_or_default()
+}
+
+/// `render [--html | --text | --json] FILE...` prints each save file in the given format.
+pub fn render_command(args: &[&str]) {
+    let (format, paths) = match args {
+        ["--html", paths @ ..] => ("html", paths),
+        ["--json", paths @ ..] => ("json", paths),
+        ["--text", paths @ ..] => ("text", paths),
+        paths => ("text", paths),
+    };
+
+    let config = Config::current();
+    for path in paths {
+        let path = Path::new(path);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("Can't read {path:?}: {err}");
+                std::process::exit(2);
+            }
+        };
+        let Some(rendered) = render(&config, &file_name, &contents) else {
+            eprintln!("{path:?} isn't a save file that can be rendered");
+            std::process::exit(2);
+        };
+        match format {
+            "html" => println!("{}", rendered.html),
+            "json" => println!("{}", rendered.json),
+            _ => println!("{}", rendered.text),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Target times to measure bests against, such as world records, a friend's PBs or our own
+//! goals, from a local TOML or JSON file like:
+//!
+//! ```toml
+//! name = "WR"
+//!
+//! [times]
+//! 1A = "1:38.240"
+//! 1FC = "3:11.5"
+//! 7C = "2:09.961"
+//! ```
+
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::trace;
+use tracing::warn;
+
+use crate::categories::Leg;
+use crate::categories::Side;
+use crate::celeste_stats::World;
+use crate::config::Config;
+use crate::dirs::DATA_DIR;
+use crate::durationutils::parse_duration;
+
+#[derive(Debug, Clone)]
+pub struct Goals {
+    /// What the times are, shown next to them, like `WR`.
+    pub name: String,
+    pub times: Vec<(Leg, Duration)>,
+}
+
+#[derive(Deserialize)]
+struct GoalsFile {
+    name: Option<String>,
+    times: BTreeMap<String, String>,
+}
+
+impl Goals {
+    /// Loads the goals file from the config's `saves.goals`, or from the data directory, if
+    /// there is one. A file that can't be read is logged and ignored.
+    pub fn load(config: &Config) -> Option<Goals> {
+        let path = match &config.goals {
+            Some(path) => path.clone(),
+            None => default_path()?,
+        };
+        match Goals::read(&path) {
+            Ok(goals) => Some(goals),
+            Err(err) => {
+                warn!("Ignoring goals in {path:?}: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Goals, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let file: GoalsFile = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents).map_err(|err| err.to_string())?
+        } else {
+            toml::from_str(&contents).map_err(|err| err.to_string())?
+        };
+
+        let mut times = Vec::new();
+        for (leg, time) in &file.times {
+            let leg = Leg::parse(leg)?;
+            let time = parse_duration(time)
+                .ok_or_else(|| format!("{time:?} isn't a time like 1:38.240"))?;
+            times.push((leg, time));
+        }
+        trace!("Loaded {} target times from {path:?}", times.len());
+
+        Ok(Goals {
+            name: file.name.unwrap_or_else(|| "goal".to_string()),
+            times,
+        })
+    }
+
+    pub fn target(&self, world: World, side: Side) -> Option<Duration> {
+        self.times
+            .iter()
+            .find(|(leg, _)| leg.world == world && leg.side == side)
+            .map(|(_, time)| *time)
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    ["goals.toml", "goals.json"]
+        .into_iter()
+        .map(|name| DATA_DIR.join(name))
+        .find(|path| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::scratch_dir;
+
+    #[test]
+    fn reads_toml() {
+        let path = scratch_dir("goals-toml").join("goals.toml");
+        std::fs::write(
+            &path,
+            "name = \"WR\"\n\n[times]\n1A = \"1:38.240\"\n1FC = \"3:11.5\"\n8C = \"2:09.961\"\n",
+        )
+        .unwrap();
+        let goals = Goals::read(&path).unwrap();
+        assert_eq!(goals.name, "WR");
+        assert_eq!(goals.times.len(), 3);
+        assert_eq!(
+            goals.target(World::ForsakenCity, Side::A),
+            Some(Duration::from_millis(98_240))
+        );
+        assert_eq!(
+            goals.target(World::ForsakenCity, Side::FullClear),
+            Some(Duration::from_millis(191_500))
+        );
+        assert_eq!(
+            goals.target(World::Core, Side::C),
+            Some(Duration::from_millis(129_961))
+        );
+        assert_eq!(goals.target(World::Core, Side::B), None);
+    }
+
+    #[test]
+    fn reads_json() {
+        let path = scratch_dir("goals-json").join("goals.json");
+        std::fs::write(&path, r#"{"times": {"7B": "10:00"}}"#).unwrap();
+        let goals = Goals::read(&path).unwrap();
+        assert_eq!(goals.name, "goal");
+        assert_eq!(
+            goals.target(World::TheSummit, Side::B),
+            Some(Duration::from_secs(600))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_goals() {
+        let dir = scratch_dir("goals-invalid");
+        assert!(Goals::read(&dir.join("missing.toml")).is_err());
+
+        let path = dir.join("goals.toml");
+        for (contents, error) in [
+            (
+                "[times]\n1A = \"soon\"\n",
+                Some(r#""soon" isn't a time like 1:38.240"#),
+            ),
+            (
+                "[times]\n1D = \"1:00\"\n",
+                Some(r#""1D" should end in A, B, C or FC"#),
+            ),
+            ("name = \"WR\"\n", None),
+            ("[times\n", None),
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            let err = Goals::read(&path).unwrap_err();
+            if let Some(error) = error {
+                assert_eq!(err, error);
+            }
+        }
+    }
+}
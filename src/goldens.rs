@@ -0,0 +1,287 @@
+//! Every golden berry there is, which ones each slot holds, and when it first held them, from
+//! the branch's history.
+
+use git2::BranchType;
+use git2::Oid;
+use git2::Repository;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use tracing::trace;
+use tracing_unwrap::ResultExt;
+
+use crate::categories::CHAPTERS;
+use crate::celeste_stats::{Stats, World, World::*};
+use crate::dirs::GIT_DIR;
+use crate::durationutils::date;
+use crate::git_repo;
+use crate::history::{slot_versions, SlotVersion};
+use crate::steam_user::SteamUser;
+use crate::stringutils::*;
+
+pub const GOLDENS_FILE: &str = "goldens.html";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Golden {
+    A(World),
+    B(World),
+    C(World),
+    /// 1A's golden, taken without dashing.
+    Winged,
+    Farewell,
+    Moon,
+}
+
+impl Golden {
+    /// Every golden, in chapter order.
+    pub fn all() -> Vec<Golden> {
+        let mut all = Vec::new();
+        for world in CHAPTERS {
+            all.push(Golden::A(world));
+            if world == ForsakenCity {
+                all.push(Golden::Winged);
+            }
+            all.push(Golden::B(world));
+            all.push(Golden::C(world));
+        }
+        all.push(Golden::Farewell);
+        all.push(Golden::Moon);
+        all
+    }
+
+    pub fn name(self) -> String {
+        let chapter = |world: World| world.name().trim().to_string();
+        match self {
+            Golden::A(world) => format!("{} A", chapter(world)),
+            Golden::B(world) => format!("{} B", chapter(world)),
+            Golden::C(world) => format!("{} C", chapter(world)),
+            Golden::Winged => format!("{} winged", chapter(ForsakenCity)),
+            Golden::Farewell => chapter(Farewell),
+            Golden::Moon => format!("{} moon berry", chapter(Farewell)),
+        }
+    }
+
+    /// Whether the save holds this golden, going by the rooms of the berries it lists, since
+    /// it doesn't mark which of them are goldens.
+    pub fn held_in(self, stats: &Stats) -> bool {
+        let world = |world: World| stats.worlds.iter().find(|w| w.world == world);
+        match self {
+            Golden::A(w) => world(w).is_some_and(|w| w.has_golden_a()),
+            Golden::B(w) => world(w).is_some_and(|w| w.has_golden_b()),
+            Golden::C(w) => world(w).is_some_and(|w| w.has_golden_c()),
+            Golden::Winged => world(ForsakenCity).is_some_and(|w| w.has_winged_golden()),
+            Golden::Farewell => world(Farewell).is_some_and(|w| w.has_golden_a()),
+            Golden::Moon => world(Farewell).is_some_and(|w| w.has_moon_berry()),
+        }
+    }
+}
+
+/// Each slot's goldens, across the branch's history.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GoldenReport {
+    pub slots: Vec<SlotGoldens>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotGoldens {
+    /// The save file, like `0.celeste`.
+    pub file: String,
+    /// The player's name in the latest version of the save.
+    pub name: String,
+    pub assisted: bool,
+    pub goldens: Vec<GoldenStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoldenStatus {
+    pub golden: Golden,
+    pub held: bool,
+    /// When the slot was first seen holding it, in seconds since the Unix epoch.
+    pub first_obtained: Option<i64>,
+}
+
+impl GoldenReport {
+    /// Goes through every commit up to `head` and then the saves in `current`, if any, which
+    /// are dated now.
+    pub fn build(repo: &Repository, head: Option<Oid>, current: &BTreeMap<String, String>) -> Self {
+        GoldenReport::from_versions(&slot_versions(repo, head, current))
+    }
+
+    pub fn from_versions(versions: &[SlotVersion]) -> Self {
+        let mut report = GoldenReport::default();
+        for version in versions {
+            report.record(&version.file, &version.stats, version.time);
+        }
+        trace!("Tracked goldens for {} slots", report.slots.len());
+        report
+    }
+
+    /// Notes which goldens a version of a slot holds, with later versions overriding what's
+    /// held now.
+    fn record(&mut self, file: &str, stats: &Stats, time: i64) {
+        let index = match self.slots.iter().position(|slot| slot.file == file) {
+            Some(index) => index,
+            None => {
+                self.slots.push(SlotGoldens {
+                    file: file.to_string(),
+                    name: String::new(),
+                    assisted: false,
+                    goldens: Golden::all()
+                        .into_iter()
+                        .map(|golden| GoldenStatus {
+                            golden,
+                            held: false,
+                            first_obtained: None,
+                        })
+                        .collect(),
+                });
+                self.slots.sort_by(|a, b| a.file.cmp(&b.file));
+                self.slots
+                    .iter()
+                    .position(|slot| slot.file == file)
+                    .unwrap_or_default()
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.name = stats.name.clone();
+        slot.assisted = stats.assisted();
+        for status in &mut slot.goldens {
+            status.held = status.golden.held_in(stats);
+            if status.held && status.first_obtained.is_none() {
+                status.first_obtained = Some(time);
+            }
+        }
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut output = String::new();
+        output.push_str(include_str!("template.html"));
+        output.push_str(&self.table());
+        output
+    }
+
+    pub fn render_text(&self) -> String {
+        self.table().strip_tags()
+    }
+
+    fn table(&self) -> String {
+        const HELD: Color = Yellow;
+        const MISSING: Color = DarkGray;
+        const ASSISTED: Color = Cyan;
+
+        let mut output = String::new();
+        for slot in &self.slots {
+            output.push_str("<pre>");
+            let held = slot.goldens.iter().filter(|status| status.held).count();
+            writeln!(
+                &mut output,
+                " {} {} {}",
+                html_escape::encode_text(&slot.name)
+                    .to_string()
+                    .underline()
+                    .color(White),
+                format!("{held}/{}", slot.goldens.len()).color(if held > 0 {
+                    HELD
+                } else {
+                    MISSING
+                }),
+                slot.file.color(MISSING)
+            )
+            .unwrap_or_log();
+
+            for status in &slot.goldens {
+                let name = status.golden.name();
+                let line = match (status.held, status.first_obtained) {
+                    (true, Some(first)) => format!("  {name:<28} {}", date(first)),
+                    (false, Some(first)) => format!("  {name:<28} lost, got {}", date(first)),
+                    (_, None) => format!("  {name:<28} -"),
+                };
+                let color = match (status.held, slot.assisted) {
+                    (true, true) => ASSISTED,
+                    (true, false) => HELD,
+                    (false, _) => MISSING,
+                };
+                writeln!(&mut output, "{}", format!("{line:<52}").color(color)).unwrap_or_log();
+            }
+            if slot.assisted && held > 0 {
+                writeln!(
+                    &mut output,
+                    " {}",
+                    "may have been taken with assists, variants or cheats".color(ASSISTED)
+                )
+                .unwrap_or_log();
+            }
+            output.push_str("</pre>");
+        }
+        output
+    }
+}
+
+/// `goldens [BRANCH]` prints the goldens on a branch of the sync repo, by default the one for
+/// the Steam user who last logged in.
+pub fn goldens_command(args: &[&str]) {
+    let branch_name = match args {
+        [] => SteamUser::most_recent()
+            .map(|user| user.branch())
+            .unwrap_or_else(|| "celeste".to_string()),
+        [branch_name] => branch_name.to_string(),
+        _ => {
+            eprintln!("Usage: goldens [BRANCH]");
+            std::process::exit(2);
+        }
+    };
+
+    let repo = git_repo();
+    let Ok(branch) = repo.find_branch(&branch_name, BranchType::Local) else {
+        eprintln!("No branch {branch_name:?} in {:?}", &*GIT_DIR);
+        std::process::exit(1);
+    };
+    let report = GoldenReport::build(&repo, branch.get().target(), &BTreeMap::new());
+    print!("{}", report.render_text());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::celeste_stats::CelesteStats;
+    use crate::config::Config;
+    use crate::game_stats::GameStats;
+    use crate::testutils::save_xml;
+
+    fn held(areas: &[(u32, u64, &[&str])]) -> Vec<Golden> {
+        let stats = CelesteStats::new(&Config::default())
+            .parse(&save_xml("Madeline", areas))
+            .unwrap();
+        Golden::all()
+            .into_iter()
+            .filter(|golden| golden.held_in(&stats))
+            .collect()
+    }
+
+    #[test]
+    fn goldens_are_told_apart_by_room() {
+        // Nineteen red berries and the golden: as many berries as the chapter has reds.
+        let mut keys = (2..=20).map(|room| format!("{room}:1")).collect::<Vec<_>>();
+        keys.push("1:12".to_string());
+        let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(held(&[(1, 1, &keys)]), [Golden::A(ForsakenCity)]);
+
+        let stats = CelesteStats::new(&Config::default())
+            .parse(&save_xml("Madeline", &[(1, 1, &keys)]))
+            .unwrap();
+        assert_eq!(stats.worlds[0].red_berries(), 19);
+
+        assert_eq!(held(&[(1, 1, &["2:1", "end:4"])]), [Golden::Winged]);
+        assert_eq!(held(&[(6, 1, &["04:2"])]), [Golden::A(Reflection)]);
+    }
+
+    #[test]
+    fn farewell_golden_and_moon_berry() {
+        assert_eq!(held(&[(10, 1, &["j-19:1"])]), [Golden::Moon]);
+        assert_eq!(held(&[(10, 1, &["intro-00-past:3"])]), [Golden::Farewell]);
+        assert_eq!(
+            held(&[(10, 1, &["intro-00-past:3", "j-19:1"])]),
+            [Golden::Farewell, Golden::Moon]
+        );
+    }
+}
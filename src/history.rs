@@ -0,0 +1,165 @@
+//! Each save slot's versions through the branch's history, and the page charting them.
+
+use git2::ObjectType;
+use git2::Oid;
+use git2::Repository;
+use git2::Sort;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tracing::trace;
+use tracing_unwrap::ResultExt;
+
+use crate::categories::{Leg, Side, CHAPTERS};
+use crate::celeste_stats::{parse_slot, Stats};
+use crate::charts;
+use crate::charts::Series;
+use crate::durationutils::*;
+use crate::stringutils::*;
+
+pub const HISTORY_FILE: &str = "history.html";
+
+/// A save slot as it was committed at some point.
+#[derive(Debug, Clone)]
+pub struct SlotVersion {
+    /// The commit time, in seconds since the Unix epoch.
+    pub time: i64,
+    /// The save file, like `0.celeste`.
+    pub file: String,
+    pub stats: Stats,
+}
+
+/// Every change to the save slots in the commits up to `head`, oldest first, followed by the
+/// saves in `current` (dated now) where they differ from the last commit.
+pub fn slot_versions(
+    repo: &Repository,
+    head: Option<Oid>,
+    current: &BTreeMap<String, String>,
+) -> Vec<SlotVersion> {
+    let mut blobs = Vec::new();
+    if let Some(head) = head {
+        let mut walk = repo.revwalk().unwrap_or_log();
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+            .unwrap_or_log();
+        walk.push(head).unwrap_or_log();
+        for oid in walk {
+            let commit = repo.find_commit(oid.unwrap_or_log()).unwrap_or_log();
+            let tree = commit.tree().unwrap_or_log();
+            for entry in tree.iter() {
+                let Some(name) = entry.name() else {
+                    continue;
+                };
+                if name.ends_with(".celeste") {
+                    blobs.push((commit.time().seconds(), name.to_string(), entry.id(), false));
+                }
+            }
+        }
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    for (name, contents) in current {
+        let blob = Oid::hash_object(ObjectType::Blob, contents.as_bytes()).unwrap_or_log();
+        blobs.push((now, name.clone(), blob, true));
+    }
+
+    let mut versions = Vec::new();
+    let mut last = BTreeMap::<String, Oid>::new();
+    // Files may go back to an earlier version, so each is kept in case it comes up again.
+    let mut parsed = BTreeMap::<Oid, Option<Stats>>::new();
+    for (time, file, blob, is_current) in blobs {
+        if last.insert(file.clone(), blob) == Some(blob) {
+            continue;
+        }
+        let stats = parsed.entry(blob).or_insert_with(|| {
+            if is_current {
+                parse_slot(&file, current.get(&file)?)
+            } else {
+                let blob = repo.find_blob(blob).ok()?;
+                parse_slot(&file, &String::from_utf8_lossy(blob.content()))
+            }
+        });
+        if let Some(stats) = stats {
+            versions.push(SlotVersion {
+                time,
+                file,
+                stats: stats.clone(),
+            });
+        }
+    }
+    trace!("Found {} versions of save slots", versions.len());
+    versions
+}
+
+/// A page charting each slot's berries and A-side bests over time.
+pub fn render_history(versions: &[SlotVersion]) -> String {
+    let mut by_file = BTreeMap::<&str, Vec<&SlotVersion>>::new();
+    for version in versions {
+        by_file.entry(&version.file).or_default().push(version);
+    }
+
+    let mut output = String::new();
+    output.push_str(include_str!("template.html"));
+    for (file, versions) in by_file {
+        let Some(latest) = versions.last() else {
+            continue;
+        };
+        output.push_str(&format!(
+            "<pre>{} {}</pre><br>",
+            html_escape::encode_text(&latest.stats.name)
+                .to_string()
+                .underline()
+                .color(White),
+            file.color(DarkGray)
+        ));
+
+        let berries = Series {
+            name: "berries".to_string(),
+            color: Red,
+            points: versions
+                .iter()
+                .map(|version| (version.time, f64::from(version.stats.total_berries)))
+                .collect(),
+        };
+        output.push_str(&charts::line_chart(
+            "Berries collected",
+            &[berries],
+            |berries| format!("{berries:.0}"),
+        ));
+
+        let palette = [Red, Yellow, Green, Cyan, Blue, Magenta, White, DarkYellow];
+        let pbs = CHAPTERS
+            .iter()
+            .zip(palette)
+            .map(|(&world, color)| Series {
+                name: Leg {
+                    world,
+                    side: Side::A,
+                }
+                .code(),
+                color,
+                points: versions
+                    .iter()
+                    .filter_map(|version| {
+                        let stats = version.stats.worlds.iter().find(|w| w.world == world)?;
+                        let time = stats.a_side.common.single_run?;
+                        Some((version.time, time.as_secs_f64()))
+                    })
+                    .collect(),
+            })
+            .filter(|series| !series.points.is_empty())
+            .collect::<Vec<_>>();
+        if !pbs.is_empty() {
+            output.push_str(&charts::line_chart("A-side bests", &pbs, |seconds| {
+                std::time::Duration::from_secs_f64(seconds)
+                    .formatted()
+                    .trim()
+                    .to_string()
+            }));
+        }
+        output.push_str("<br>");
+    }
+    output
+}
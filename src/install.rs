@@ -1,68 +1,67 @@
 use std::fs;
 use std::os::unix::prelude::PermissionsExt;
 
-use itertools::Itertools;
 use steamlocate::SteamDir;
 use tracing::info;
+use tracing::warn;
 use tracing_unwrap::OptionExt;
 use tracing_unwrap::ResultExt;
 
 use crate::dirs::BIN_DIR;
+use crate::dirs::DATA_DIR;
 use crate::dirs::ETC_DIR;
-use crate::dirs::STEAM_USER_DATA_DIR;
+use crate::dirs::GIT_DIR;
+use crate::dirs::SYSTEMD_USER_DIR;
+use crate::steam_shortcut;
+use crate::steam_shortcut::Shortcut;
 use crate::NAME;
 
-use steamlocate;
+pub const APP_NAME: &str = "Celeste with Sync";
 
-pub fn install() {
-    let argv = std::env::args().collect_vec();
-    let own_binary = fs::read(&argv[0]).unwrap_or_log();
+/// Steam library artwork, named as Steam names it in `config/grid` with `0` in place of the
+/// shortcut's app ID.
+pub static ARTWORK: &[(&str, &[u8])] = &[
+    ("0.png", include_bytes!("../assets/0.png")),
+    ("0p.png", include_bytes!("../assets/0p.png")),
+    ("0_hero.png", include_bytes!("../assets/0_hero.png")),
+    ("0_logo.png", include_bytes!("../assets/0_logo.png")),
+    ("0_icon.png", include_bytes!("../assets/0_icon.png")),
+    ("0.json", include_bytes!("../assets/0.json")),
+];
 
-    let mut steam = SteamDir::locate().unwrap_or_log();
-    let shortcuts = steam.shortcuts();
+pub fn shortcut() -> Shortcut {
+    Shortcut {
+        app_name: APP_NAME.to_string(),
+        exe: BIN_DIR.join(NAME),
+        start_dir: BIN_DIR.clone(),
+        icon: ETC_DIR.join("0_icon.png"),
+    }
+}
 
-    dbg!(shortcuts);
-    return;
+pub fn install() {
+    let own_binary = fs::read(std::env::current_exe().unwrap_or_log()).unwrap_or_log();
 
     info!("Installing.");
 
     let bin_path = BIN_DIR.join(NAME);
     fs::create_dir_all(&*BIN_DIR).unwrap_or_log();
-    fs::write(&bin_path, &own_binary).unwrap_or_log();
-    let mut perms = fs::metadata(&bin_path).unwrap_or_log().permissions();
+    // The watcher service may be running the installed binary, which can't be written to
+    // while it runs, but can be replaced; the service picks up the new one when restarted.
+    let temp_path = BIN_DIR.join(format!(".{NAME}.tmp"));
+    fs::write(&temp_path, &own_binary).unwrap_or_log();
+    let mut perms = fs::metadata(&temp_path).unwrap_or_log().permissions();
     perms.set_mode(0o755);
-    fs::set_permissions(&bin_path, perms).unwrap_or_log();
+    fs::set_permissions(&temp_path, perms).unwrap_or_log();
+    fs::rename(&temp_path, &bin_path).unwrap_or_log();
     let bin_path = bin_path.to_str().unwrap_or_log();
 
-    let icon_path = ETC_DIR.join(format!("0_icon.png"));
     fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&icon_path, include_bytes!("../assets/0_icon.png")).unwrap_or_log();
+    for (name, bytes) in ARTWORK {
+        fs::write(ETC_DIR.join(name), bytes).unwrap_or_log();
+    }
+    let icon_path = ETC_DIR.join("0_icon.png");
     let icon_path = icon_path.to_str().unwrap_or_log();
 
-    let hero_path = ETC_DIR.join(format!("0_hero.png"));
-    fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&hero_path, include_bytes!("../assets/0_hero.png")).unwrap_or_log();
-
-    let capsule_path = ETC_DIR.join(format!("0.png"));
-    fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&capsule_path, include_bytes!("../assets/0.png")).unwrap_or_log();
-
-    let logo_path = ETC_DIR.join(format!("0_logo.png"));
-    fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&logo_path, include_bytes!("../assets/0_logo.png")).unwrap_or_log();
-
-    let logo_path = ETC_DIR.join(format!("0_logo.png"));
-    fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&logo_path, include_bytes!("../assets/0_logo.png")).unwrap_or_log();
-
-    let logo_config_path = ETC_DIR.join(format!("0.json"));
-    fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&logo_config_path, include_bytes!("../assets/0.json")).unwrap_or_log();
-
-    let poster_path = ETC_DIR.join(format!("0p.png"));
-    fs::create_dir_all(&*ETC_DIR).unwrap_or_log();
-    fs::write(&poster_path, include_bytes!("../assets/0p.png")).unwrap_or_log();
-
     let desktop_path = ETC_DIR.join(format!("{NAME}.desktop"));
     fs::write(
         &desktop_path,
@@ -70,7 +69,7 @@ pub fn install() {
             "#!/usr/bin/env xdg-open
 [Desktop Entry]
 Type=Application
-Name=Celeste with Sync
+Name={APP_NAME}
 Comment=Play Celeste and sync saves to git
 Categories=Game
 Exec={bin_path}
@@ -88,4 +87,185 @@ Icon={icon_path}
     cmd.arg("install");
     cmd.arg(&desktop_path);
     cmd.status().unwrap_or_log();
+
+    install_service(bin_path);
+
+    let Some(steam) = SteamDir::locate() else {
+        warn!("Couldn't find Steam, so not adding a Steam shortcut.");
+        return;
+    };
+
+    let shortcut = shortcut();
+    let user_config_dirs = steam_shortcut::user_config_dirs(&steam.path);
+    if user_config_dirs.is_empty() {
+        warn!("No Steam accounts found, so not adding a Steam shortcut.");
+        return;
+    }
+
+    if !shortcuts_readable(&user_config_dirs) {
+        warn!("Not adding a Steam shortcut, so as not to lose the ones already there.");
+        return;
+    }
+
+    let was_running = steam_shortcut::stop_steam();
+    for user_config_dir in user_config_dirs {
+        match shortcut.install(&user_config_dir, ARTWORK) {
+            Ok(paths) => {
+                for path in paths {
+                    info!("Wrote {path:?}");
+                }
+            }
+            Err(err) => warn!("Couldn't add the Steam shortcut: {err}"),
+        }
+    }
+    if was_running {
+        steam_shortcut::start_steam();
+    }
+
+    info!(
+        "Installed {APP_NAME:?} as Steam shortcut {}.",
+        shortcut.app_id()
+    );
+    info!("Alternatively, set Celeste's launch options in Steam to: {bin_path} %command%");
+}
+
+fn service_name() -> String {
+    format!("{NAME}.service")
+}
+
+/// Installs and starts a systemd user service running `watch`, so sessions started from the
+/// Steam library are synced too.
+fn install_service(bin_path: &str) {
+    let service_path = SYSTEMD_USER_DIR.join(service_name());
+    fs::create_dir_all(&*SYSTEMD_USER_DIR).unwrap_or_log();
+    fs::write(
+        &service_path,
+        format!(
+            "[Unit]
+Description=Sync Celeste saves to git after every session
+
+[Service]
+ExecStart={bin_path} watch
+Restart=on-failure
+Nice=10
+
+[Install]
+WantedBy=default.target
+"
+        ),
+    )
+    .unwrap_or_log();
+    info!("Wrote {service_path:?}");
+
+    // Restarting picks up the binary we just replaced, if the service was already running.
+    for args in [
+        &["daemon-reload"][..],
+        &["enable", &service_name()],
+        &["restart", &service_name()],
+    ] {
+        let status = std::process::Command::new("systemctl")
+            .arg("--user")
+            .args(args)
+            .status();
+        if !status.map(|status| status.success()).unwrap_or(false) {
+            warn!("Couldn't enable the watcher service; `systemctl --user {args:?}` failed.");
+            return;
+        }
+    }
+    info!("Enabled and started {}.", service_name());
+}
+
+/// Reverses every step of [`install`], printing each thing removed. The git history is kept
+/// unless `purge` is set, in which case everything under `DATA_DIR` goes.
+pub fn uninstall(purge: bool) {
+    info!("Uninstalling.");
+
+    let mut removed = Vec::new();
+
+    let desktop_path = ETC_DIR.join(format!("{NAME}.desktop"));
+    if desktop_path.exists() {
+        info!("Attempting to uninstall with xdg-desktop-menu.");
+        let mut cmd = std::process::Command::new("xdg-desktop-menu");
+        cmd.arg("uninstall");
+        cmd.arg(&desktop_path);
+        if cmd.status().map(|status| status.success()).unwrap_or(false) {
+            removed.push(format!("desktop menu entry {NAME}.desktop"));
+        }
+    }
+
+    let service_path = SYSTEMD_USER_DIR.join(service_name());
+    if service_path.exists() {
+        std::process::Command::new("systemctl")
+            .args(["--user", "disable", "--now", &service_name()])
+            .status()
+            .ok();
+        if fs::remove_file(&service_path).is_ok() {
+            removed.push(format!("watcher service {service_path:?}"));
+        }
+        std::process::Command::new("systemctl")
+            .args(["--user", "daemon-reload"])
+            .status()
+            .ok();
+    }
+
+    if let Some(steam) = SteamDir::locate() {
+        let shortcut = shortcut();
+        let user_config_dirs = steam_shortcut::user_config_dirs(&steam.path);
+        if !user_config_dirs.is_empty() && shortcuts_readable(&user_config_dirs) {
+            let was_running = steam_shortcut::stop_steam();
+            for user_config_dir in user_config_dirs {
+                match shortcut.uninstall(&user_config_dir, ARTWORK) {
+                    Ok(paths) => removed.extend(paths),
+                    Err(err) => warn!("Couldn't remove the Steam shortcut: {err}"),
+                }
+            }
+            if was_running {
+                steam_shortcut::start_steam();
+            }
+        }
+    }
+
+    let mut paths = vec![desktop_path, BIN_DIR.join(NAME)];
+    paths.extend(ARTWORK.iter().map(|(name, _)| ETC_DIR.join(name)));
+    for path in paths {
+        if fs::remove_file(&path).is_ok() {
+            removed.push(format!("{path:?}"));
+        }
+    }
+    for dir in [&*ETC_DIR, &*BIN_DIR] {
+        if fs::remove_dir(dir).is_ok() {
+            removed.push(format!("{dir:?}"));
+        }
+    }
+
+    if purge {
+        if fs::remove_dir_all(&*DATA_DIR).is_ok() {
+            removed.push(format!("{:?}, including the git history", &*DATA_DIR));
+        }
+    } else if GIT_DIR.exists() {
+        info!(
+            "Keeping git history in {:?}; use --purge to remove it.",
+            &*GIT_DIR
+        );
+    }
+
+    if removed.is_empty() {
+        println!("Nothing to remove.");
+    }
+    for thing in removed {
+        println!("Removed {thing}");
+    }
+}
+
+/// Whether every account's `shortcuts.vdf` can be read and parsed, logging any that can't.
+/// Checked before stopping Steam, so that it isn't shut down for nothing.
+fn shortcuts_readable(user_config_dirs: &[std::path::PathBuf]) -> bool {
+    let mut readable = true;
+    for user_config_dir in user_config_dirs {
+        if let Err(err) = steam_shortcut::read_shortcuts(user_config_dir) {
+            warn!("{err}");
+            readable = false;
+        }
+    }
+    readable
 }
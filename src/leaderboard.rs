@@ -0,0 +1,389 @@
+//! Everyone's bests ranked against each other, per chapter side and per category: ours, and
+//! those of friends whose sync repos are fetched into `refs/remotes/<remote>/`.
+
+use git2::BranchType;
+use git2::Repository;
+use git2::Tree;
+use std::fmt::Write;
+use std::time::Duration;
+use tracing::trace;
+use tracing::warn;
+use tracing_unwrap::ResultExt;
+
+use crate::categories::Category;
+use crate::celeste_stats::{parse_slot, Stats};
+use crate::compare::{truncate, Comparison};
+use crate::config::Config;
+use crate::durationutils::*;
+use crate::git_repo;
+use crate::steam_user::SteamUser;
+use crate::stringutils::*;
+
+pub const LEADERBOARD_FILE: &str = "leaderboard.html";
+
+/// Someone on the leaderboard, with all of their save slots.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub name: String,
+    pub slots: Vec<Stats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Leaderboard {
+    pub players: Vec<String>,
+    /// A board per chapter side, then one per category, leaving out those nobody has a time
+    /// for.
+    pub boards: Vec<Board>,
+}
+
+/// One chapter side's or category's ranking.
+#[derive(Debug, Clone)]
+pub struct Board {
+    /// The leg's code, or empty for a category.
+    pub code: String,
+    pub name: String,
+    /// Each player's rank, index into [`Leaderboard::players`] and time, fastest first. Tied
+    /// times share a rank.
+    pub ranking: Vec<(usize, usize, Duration)>,
+}
+
+impl Leaderboard {
+    /// Ranks each player by their best slot. Slots played with assists, variants or cheats are
+    /// left out unless the config's `saves.includeAssisted` lets them in, and a category only
+    /// counts once every leg of it is done.
+    pub fn new(players: &[Player], config: &Config) -> Self {
+        let slots = players
+            .iter()
+            .map(|player| {
+                player
+                    .slots
+                    .iter()
+                    .filter(|stats| config.include_assisted || !stats.assisted())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let board = |code: String, name: String, time: &dyn Fn(&Stats) -> Option<Duration>| {
+            let mut times = slots
+                .iter()
+                .enumerate()
+                .filter_map(|(player, slots)| {
+                    let best = slots.iter().filter_map(|stats| time(stats)).min()?;
+                    Some((player, best))
+                })
+                .collect::<Vec<_>>();
+            times.sort_by_key(|(_, time)| *time);
+            let ranking = times
+                .iter()
+                .map(|&(player, time)| {
+                    let rank = 1 + times.iter().filter(|(_, other)| *other < time).count();
+                    (rank, player, time)
+                })
+                .collect::<Vec<_>>();
+            Board {
+                code,
+                name,
+                ranking,
+            }
+        };
+
+        let mut boards = Vec::new();
+        for leg in Comparison::legs() {
+            boards.push(board(leg.code(), leg.name(), &|stats| leg.time(stats)));
+        }
+        for category in Category::all(config) {
+            boards.push(board(String::new(), category.name.clone(), &|stats| {
+                let sum = category.sum_of_bests(stats);
+                sum.complete().then_some(sum.time)
+            }));
+        }
+        boards.retain(|board| !board.ranking.is_empty());
+
+        Leaderboard {
+            players: players.iter().map(|player| player.name.clone()).collect(),
+            boards,
+        }
+    }
+
+    /// How many boards each player tops, ties included.
+    pub fn firsts(&self) -> Vec<u32> {
+        let mut firsts = vec![0; self.players.len()];
+        for board in &self.boards {
+            for &(rank, player, _) in &board.ranking {
+                if rank == 1 {
+                    firsts[player] += 1;
+                }
+            }
+        }
+        firsts
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut output = String::new();
+        output.push_str(include_str!("template.html"));
+        output.push_str(&self.table());
+        output
+    }
+
+    pub fn render_text(&self) -> String {
+        self.table().strip_tags()
+    }
+
+    fn table(&self) -> String {
+        const HEADER_FG: Color = Black;
+        const HEADER_BG: Color = White;
+        const FIRST: Color = Yellow;
+        const SECOND: Color = White;
+        const THIRD: Color = DarkYellow;
+        const REST: Color = DarkGray;
+
+        let width = 34 + 28 * self.players.len().min(4);
+        let divider = |output: &mut String, title: &str| {
+            writeln!(
+                output,
+                "{}",
+                format!("  {title:<width$}")
+                    .color(HEADER_FG)
+                    .background(HEADER_BG)
+            )
+            .unwrap_or_log();
+        };
+
+        let mut output = String::new();
+        output.push_str("<pre>");
+        let mut categories = false;
+        divider(&mut output, "Chapters");
+        for board in &self.boards {
+            if board.code.is_empty() && !categories {
+                categories = true;
+                output.push('\n');
+                divider(&mut output, "Categories");
+            }
+            write!(
+                output,
+                "  {:<32}",
+                html_escape::encode_text(&truncate(
+                    &format!("{:<4} {}", board.code, board.name),
+                    32
+                ))
+            )
+            .unwrap_or_log();
+            for &(rank, player, time) in &board.ranking {
+                let color = match rank {
+                    1 => FIRST,
+                    2 => SECOND,
+                    3 => THIRD,
+                    _ => REST,
+                };
+                let entry = format!(
+                    "{rank:>2}. {:<10} {}",
+                    truncate(&self.players[player], 10),
+                    time.formatted()
+                );
+                write!(
+                    output,
+                    " {}",
+                    html_escape::encode_text(&entry).to_string().color(color)
+                )
+                .unwrap_or_log();
+            }
+            output.push('\n');
+        }
+
+        output.push('\n');
+        divider(&mut output, "Firsts");
+        let firsts = self.firsts();
+        for (player, firsts) in self.players.iter().zip(firsts) {
+            writeln!(
+                output,
+                "  {:<32} {}",
+                html_escape::encode_text(&truncate(player, 32)),
+                format!("{firsts:>4}").color(if firsts > 0 { FIRST } else { REST })
+            )
+            .unwrap_or_log();
+        }
+        output.push_str("</pre>");
+        output
+    }
+}
+
+/// The save slots committed in a tree.
+pub fn slots(repo: &Repository, tree: &Tree) -> Vec<Stats> {
+    tree.iter()
+        .filter_map(|entry| {
+            let name = entry.name()?;
+            let blob = repo.find_blob(entry.id()).ok()?;
+            parse_slot(name, &String::from_utf8_lossy(blob.content()))
+        })
+        .collect()
+}
+
+/// Each friend in the config, with the slots of every branch last fetched from them, so
+/// they're ranked by their best slot on any of their accounts. Friends that haven't been
+/// fetched yet are left out.
+pub fn friends(repo: &Repository, config: &Config) -> Vec<Player> {
+    config
+        .friends
+        .iter()
+        .filter_map(|friend| {
+            let pattern = friend.remote_refs();
+            let mut slots = Vec::new();
+            let mut branches = 0;
+            for reference in repo.references_glob(&pattern).ok()?.flatten() {
+                let Ok(tree) = reference.peel_to_tree() else {
+                    continue;
+                };
+                branches += 1;
+                slots.extend(self::slots(repo, &tree));
+            }
+            if branches == 0 {
+                warn!(
+                    "Nothing in {pattern}, leaving {} off the leaderboard",
+                    friend.name
+                );
+                return None;
+            }
+            trace!(
+                "Found {} slots for {} in {branches} branches of {pattern}",
+                slots.len(),
+                friend.name
+            );
+            Some(Player {
+                name: friend.name.clone(),
+                slots,
+            })
+        })
+        .collect()
+}
+
+/// `leaderboard [BRANCH]` prints the leaderboard from what was last fetched from friends,
+/// with our own slots from a branch of the sync repo, by default the one for the Steam user
+/// who last logged in.
+pub fn leaderboard_command(args: &[&str]) {
+    let user = SteamUser::most_recent();
+    let branch_name = match args {
+        [] => user
+            .as_ref()
+            .map(|user| user.branch())
+            .unwrap_or_else(|| "celeste".to_string()),
+        [branch_name] => branch_name.to_string(),
+        _ => {
+            eprintln!("Usage: leaderboard [BRANCH]");
+            std::process::exit(2);
+        }
+    };
+
+    let repo = git_repo();
+    let config = Config::load(&repo);
+    let mut players = Vec::new();
+    if let Ok(branch) = repo.find_branch(&branch_name, BranchType::Local) {
+        players.push(Player {
+            name: own_name(user.as_ref()),
+            slots: slots(&repo, &branch.get().peel_to_tree().unwrap_or_log()),
+        });
+    }
+    players.extend(friends(&repo, &config));
+    print!("{}", Leaderboard::new(&players, &config).render_text());
+}
+
+/// What the leaderboard calls us: the Steam persona name, if we know who's playing.
+pub fn own_name(user: Option<&SteamUser>) -> String {
+    user.map(|user| user.persona_name.clone())
+        .unwrap_or_else(|| "me".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::celeste_stats::CelesteStats;
+    use crate::config::Friend;
+    use crate::game_stats::GameStats;
+    use crate::sync::fetch_friend;
+    use crate::testutils::{save_xml, scratch_dir};
+    use git2::Signature;
+    use std::path::Path;
+
+    /// Commits a save slot with a 1A best of `seconds` to `branch` of the bare repo at `path`.
+    fn commit_slot(path: &Path, branch: &str, seconds: u64) {
+        let repo = Repository::open_bare(path)
+            .or_else(|_| Repository::init_bare(path))
+            .unwrap();
+        let save = save_xml(branch, &[(1, seconds * 10_000_000, &[])]);
+        let blob = repo.blob(save.as_bytes()).unwrap();
+        let mut tree = repo.treebuilder(None).unwrap();
+        tree.insert("0.celeste", blob, 0o100_644).unwrap();
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        let signature = Signature::now("Madeline", "madeline@example.com").unwrap();
+        repo.commit(
+            Some(&format!("refs/heads/{branch}")),
+            &signature,
+            &signature,
+            "save",
+            &tree,
+            &[],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn ranks_fetched_friends() {
+        let dir = scratch_dir("leaderboard");
+        // Alice has played on two accounts, with her best on the older `celeste` branch.
+        commit_slot(&dir.join("alice.git"), "celeste-Alice", 100);
+        commit_slot(&dir.join("alice.git"), "celeste", 90);
+        // Bob only shares one branch, so his faster time elsewhere doesn't count.
+        commit_slot(&dir.join("bob.git"), "celeste-Bob", 95);
+        commit_slot(&dir.join("bob.git"), "celeste", 80);
+
+        let own = dir.join("own.git");
+        let repo = Repository::init_bare(&own).unwrap();
+        let friend = |remote: &str, name: &str, branch: Option<&str>| Friend {
+            remote: remote.to_string(),
+            name: name.to_string(),
+            url: dir.join(format!("{remote}.git")).display().to_string(),
+            branch: branch.map(str::to_string),
+        };
+        let config = Config {
+            friends: vec![
+                friend("alice", "Alice", None),
+                friend("bob", "Bob", Some("celeste-Bob")),
+                friend("carol", "Carol", None),
+            ],
+            ..Config::default()
+        };
+        assert!(fetch_friend(&own, &config.friends[0]));
+        assert!(fetch_friend(&own, &config.friends[1]));
+        // Carol's repo doesn't exist, so she's left off rather than stopping the rest.
+        assert!(!fetch_friend(&own, &config.friends[2]));
+
+        let me = CelesteStats::new(&config)
+            .parse(&save_xml("me", &[(1, 970_000_000, &[])]))
+            .unwrap();
+        let mut players = vec![Player {
+            name: "me".to_string(),
+            slots: vec![me],
+        }];
+        players.extend(friends(&repo, &config));
+        assert_eq!(
+            players.iter().map(|p| p.slots.len()).collect::<Vec<_>>(),
+            [1, 2, 1]
+        );
+
+        let leaderboard = Leaderboard::new(&players, &config);
+        assert_eq!(leaderboard.players, ["me", "Alice", "Bob"]);
+        let board = leaderboard
+            .boards
+            .iter()
+            .find(|board| board.code == "1A")
+            .unwrap();
+        assert_eq!(
+            board.ranking,
+            [
+                (1, 1, Duration::from_secs(90)),
+                (2, 2, Duration::from_secs(95)),
+                (3, 0, Duration::from_secs(97)),
+            ]
+        );
+        assert_eq!(leaderboard.firsts(), [0, 1, 0]);
+    }
+}
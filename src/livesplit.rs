@@ -0,0 +1,278 @@
+//! LiveSplit `.lss` splits: exported from a save's bests for a category, and imported to
+//! compare a timer's best segments with them.
+
+use minidom::Element;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+use tracing_unwrap::OptionExt;
+use tracing_unwrap::ResultExt;
+
+use crate::categories::Category;
+use crate::categories::Leg;
+use crate::celeste_stats::Stats;
+use crate::config::Config;
+use crate::durationutils::*;
+
+/// A splits file for `category`, with a segment per leg. Each segment's best is the leg's best
+/// in the save, and the personal best splits are the running sum of those, since the game
+/// doesn't record whole runs.
+pub fn export(category: &Category, stats: &Stats) -> String {
+    let mut output = String::new();
+    writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap_or_log();
+    writeln!(output, r#"<Run version="1.7.0">"#).unwrap_or_log();
+    writeln!(output, "  <GameIcon />").unwrap_or_log();
+    writeln!(output, "  <GameName>Celeste</GameName>").unwrap_or_log();
+    writeln!(
+        output,
+        "  <CategoryName>{}</CategoryName>",
+        html_escape::encode_text(&category.name)
+    )
+    .unwrap_or_log();
+    // LiveSplit 1.7 expects the speedrun.com details, even when they're empty.
+    writeln!(output, "  <Metadata>").unwrap_or_log();
+    writeln!(output, r#"    <Run id="" />"#).unwrap_or_log();
+    writeln!(output, r#"    <Platform usesEmulator="False"></Platform>"#).unwrap_or_log();
+    writeln!(output, "    <Region></Region>").unwrap_or_log();
+    writeln!(output, "    <Variables />").unwrap_or_log();
+    writeln!(output, "  </Metadata>").unwrap_or_log();
+    writeln!(output, "  <Offset>00:00:00</Offset>").unwrap_or_log();
+    writeln!(output, "  <AttemptCount>0</AttemptCount>").unwrap_or_log();
+    writeln!(output, "  <AttemptHistory />").unwrap_or_log();
+    writeln!(output, "  <Segments>").unwrap_or_log();
+
+    // Once a leg has no best, neither do the running sums after it.
+    let mut split = Some(Duration::ZERO);
+    for leg in &category.legs {
+        let best = leg.time(stats);
+        split = split.zip(best).map(|(split, best)| split + best);
+
+        writeln!(output, "    <Segment>").unwrap_or_log();
+        writeln!(
+            output,
+            "      <Name>{}</Name>",
+            html_escape::encode_text(&leg.name())
+        )
+        .unwrap_or_log();
+        writeln!(output, "      <Icon />").unwrap_or_log();
+        writeln!(output, "      <SplitTimes>").unwrap_or_log();
+        writeln!(
+            output,
+            r#"        <SplitTime name="Personal Best">{}</SplitTime>"#,
+            times(split)
+        )
+        .unwrap_or_log();
+        writeln!(output, "      </SplitTimes>").unwrap_or_log();
+        writeln!(
+            output,
+            "      <BestSegmentTime>{}</BestSegmentTime>",
+            times(best)
+        )
+        .unwrap_or_log();
+        writeln!(output, "      <SegmentHistory />").unwrap_or_log();
+        writeln!(output, "    </Segment>").unwrap_or_log();
+    }
+
+    writeln!(output, "  </Segments>").unwrap_or_log();
+    writeln!(output, "  <AutoSplitterSettings />").unwrap_or_log();
+    writeln!(output, "</Run>").unwrap_or_log();
+    output
+}
+
+/// Celeste's timer counts game time, but we fill in real time as well so the splits show up
+/// in LiveSplit's default timing method.
+fn times(time: Option<Duration>) -> String {
+    match time {
+        Some(time) => format!(
+            "<RealTime>{0}</RealTime><GameTime>{0}</GameTime>",
+            time.livesplit()
+        ),
+        None => String::new(),
+    }
+}
+
+/// One segment of an imported splits file.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub name: String,
+    pub best: Option<Duration>,
+}
+
+/// Reads the segments of a splits file.
+pub fn import(contents: &str) -> Result<Vec<Segment>, String> {
+    let run = contents
+        .trim_start_matches('\u{feff}')
+        .parse::<Element>()
+        .map_err(|err| err.to_string())?;
+    if run.name() != "Run" {
+        return Err(format!("expected a <Run>, found <{}>", run.name()));
+    }
+
+    let time = |el: Option<&Element>| {
+        let el = el?;
+        ["GameTime", "RealTime"]
+            .into_iter()
+            .find_map(|timing| child(el, timing))
+            .and_then(|time| parse_duration(&time.text()))
+    };
+
+    let segments = child(&run, "Segments").ok_or("there are no <Segments>")?;
+    Ok(segments
+        .children()
+        .map(|segment| Segment {
+            name: child(segment, "Name")
+                .map(|name| name.text().trim().to_string())
+                .unwrap_or_default(),
+            best: time(child(segment, "BestSegmentTime")),
+        })
+        .collect())
+}
+
+fn child<'a>(el: &'a Element, name: &str) -> Option<&'a Element> {
+    el.children().find(|child| child.name() == name)
+}
+
+/// The leg a segment is for, by its name or code, like `Forsaken City B-Side` or `1B`.
+fn leg_for(segment: &Segment, legs: &[Leg]) -> Option<Leg> {
+    let name = segment.name.to_lowercase();
+    legs.iter()
+        .find(|leg| leg.name().to_lowercase() == name || leg.code().to_lowercase() == name)
+        .copied()
+}
+
+/// A table of a splits file's best segments against the save's bests.
+pub fn compare(segments: &[Segment], category: &Category, stats: &Stats) -> String {
+    let mut output = String::new();
+    writeln!(
+        output,
+        " {} splits against {}'s bests",
+        category.name, stats.name
+    )
+    .unwrap_or_log();
+    writeln!(
+        output,
+        "  {:<28} {:>13} {:>13} {:>14}",
+        "segment", "splits best", "in-game best", "difference"
+    )
+    .unwrap_or_log();
+
+    let show = |time: Option<Duration>| {
+        time.map(|time| time.formatted())
+            .unwrap_or_else(|| format!("{:>13}", "-"))
+    };
+    for segment in segments {
+        let in_game = leg_for(segment, &category.legs).and_then(|leg| leg.time(stats));
+        let difference = match (segment.best, in_game) {
+            (Some(splits), Some(in_game)) if in_game == splits => "same".to_string(),
+            (Some(splits), Some(in_game)) if in_game < splits => {
+                format!("-{}", (splits - in_game).formatted().trim())
+            }
+            (Some(splits), Some(in_game)) => format!("+{}", (in_game - splits).formatted().trim()),
+            _ => String::new(),
+        };
+        writeln!(
+            output,
+            "  {:<28} {} {} {:>14}",
+            segment.name,
+            show(segment.best),
+            show(in_game),
+            difference
+        )
+        .unwrap_or_log();
+    }
+    output
+}
+
+/// `livesplit export SAVE [CATEGORY]` prints a splits file for a category (Any% by default),
+/// and `livesplit compare SAVE SPLITS [CATEGORY]` compares a splits file with the save.
+pub fn livesplit_command(args: &[&str]) {
+    let categories = Category::all(&Config::current());
+    let category = |name: Option<&str>| {
+        let name = name.unwrap_or("Any%");
+        let found = categories
+            .iter()
+            .find(|category| category.name.eq_ignore_ascii_case(name));
+        if found.is_none() {
+            eprintln!(
+                "No category {name:?}; there's {}",
+                categories
+                    .iter()
+                    .map(|c| c.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            std::process::exit(2);
+        }
+        found.unwrap_or_log()
+    };
+
+    match args {
+        ["export", save, rest @ ..] if rest.len() <= 1 => {
+            let stats = read_save(save);
+            print!("{}", export(category(rest.first().copied()), &stats));
+        }
+        ["compare", save, splits, rest @ ..] if rest.len() <= 1 => {
+            let stats = read_save(save);
+            let contents = std::fs::read_to_string(splits).unwrap_or_log();
+            let segments = import(&contents).unwrap_or_else(|err| {
+                eprintln!("Can't read {splits:?}: {err}");
+                std::process::exit(1);
+            });
+            print!(
+                "{}",
+                compare(&segments, category(rest.first().copied()), &stats)
+            );
+        }
+        _ => {
+            eprintln!("Usage: livesplit export SAVE [CATEGORY]");
+            eprintln!("       livesplit compare SAVE SPLITS [CATEGORY]");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn read_save(path: &str) -> Stats {
+    let contents = std::fs::read_to_string(Path::new(path)).unwrap_or_log();
+    Stats::from_save(&contents.parse::<Element>().unwrap_or_log())
+        .unwrap_or_else(|| panic!("{path:?} isn't a save slot"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::celeste_stats::CelesteStats;
+    use crate::game_stats::GameStats;
+    use crate::testutils::save_xml;
+
+    #[test]
+    fn export_round_trips() {
+        let stats = CelesteStats::new(&Config::default())
+            .parse(&save_xml(
+                "Madeline",
+                &[(1, 982_400_000, &[]), (2, 1_696_470_000, &[])],
+            ))
+            .unwrap();
+        let category = &Category::well_known()[0];
+        assert_eq!(category.name, "Any%");
+
+        let exported = export(category, &stats);
+        let run = exported.parse::<Element>().unwrap();
+        assert!(child(&run, "Metadata").is_some());
+
+        let segments = import(&exported).unwrap();
+        assert_eq!(segments.len(), category.legs.len());
+        for (segment, leg) in segments.iter().zip(&category.legs) {
+            assert_eq!(segment.name, leg.name());
+            assert_eq!(segment.best, leg.time(&stats));
+        }
+        assert_eq!(segments[0].best, Some(Duration::from_millis(98_240)));
+        assert_eq!(segments[1].best, Some(Duration::from_millis(169_647)));
+    }
+
+    #[test]
+    fn import_rejects_other_files() {
+        assert!(import("<Layout />").is_err());
+        assert!(import("<Run />").is_err());
+        assert!(import("not xml").is_err());
+    }
+}
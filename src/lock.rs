@@ -0,0 +1,240 @@
+use once_cell::sync::Lazy;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tracing::debug;
+use tracing::info;
+use tracing::instrument;
+use tracing::warn;
+use tracing_unwrap::ResultExt;
+
+use crate::dirs::DATA_DIR;
+
+pub static LOCK_PATH: Lazy<PathBuf> = Lazy::new(|| DATA_DIR.join("daemon.lock"));
+
+/// The lock file while this process holds it, and what it says.
+static HELD: Mutex<Option<Held>> = Mutex::new(None);
+
+struct Held {
+    file: File,
+    info: LockInfo,
+}
+
+/// What the syncing process is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    Starting,
+    Fetching,
+    Launching,
+    Watching,
+    Settling,
+    Committing,
+    Pushing,
+}
+
+impl Phase {
+    /// Whether the game session this phase belongs to is over, so a new one could start.
+    pub fn after_game(self) -> bool {
+        matches!(self, Phase::Settling | Phase::Committing | Phase::Pushing)
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Starting => "starting",
+            Phase::Fetching => "fetching",
+            Phase::Launching => "waiting for launch",
+            Phase::Watching => "watching game process",
+            Phase::Settling => "waiting for saves to settle",
+            Phase::Committing => "committing",
+            Phase::Pushing => "pushing",
+        })
+    }
+}
+
+/// The contents of the lock file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: i32,
+    /// The holder's start time in clock ticks after boot, which with the PID identifies it.
+    pub start_time: u64,
+    pub phase: Phase,
+    /// When the holder took the lock, in seconds since the Unix epoch.
+    pub since: u64,
+}
+
+impl LockInfo {
+    fn current(phase: Phase) -> Self {
+        let pid = std::process::id() as i32;
+        LockInfo {
+            pid,
+            start_time: start_time(pid).unwrap_or_default(),
+            phase,
+            since: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    /// Reads the lock file, if there is one. Its holder may be halfway through writing it, so
+    /// contents that don't parse are `None` rather than anything to act on.
+    pub fn read() -> Option<LockInfo> {
+        let contents = fs::read_to_string(&*LOCK_PATH).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(info) => Some(info),
+            Err(err) => {
+                debug!("Couldn't read lock file {:?}: {err}", &*LOCK_PATH);
+                None
+            }
+        }
+    }
+
+    /// The live process holding the lock, if any.
+    pub fn live() -> Option<LockInfo> {
+        let file = open().ok()?;
+        if try_lock(&file) {
+            // Nobody holds it; dropping the file lets go again.
+            return None;
+        }
+        holder(&file)
+    }
+}
+
+fn start_time(pid: i32) -> Option<u64> {
+    Some(
+        procfs::process::Process::new(pid)
+            .ok()?
+            .stat()
+            .ok()?
+            .starttime,
+    )
+}
+
+/// The lock file, which is created once and never removed or replaced, since a process could
+/// be waiting to lock the very file that's gone.
+fn open() -> io::Result<File> {
+    fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&*LOCK_PATH)
+}
+
+/// Takes an exclusive flock(2) on the lock file without waiting. The kernel lets go of it when
+/// the file is closed, including when the process dies, so there's never a stale lock to
+/// clean up.
+fn try_lock(file: &File) -> bool {
+    // SAFETY: `file` keeps the fd open for the whole call, and flock(2) only takes it and
+    // the flags by value.
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+/// Who holds the lock on `file`, once they've written it. Returns `None` if they let go of it
+/// in the meantime.
+fn holder(file: &File) -> Option<LockInfo> {
+    loop {
+        // Whoever holds it writes what they're doing right after taking it.
+        if let Some(info) = LockInfo::read() {
+            return Some(info);
+        }
+        sleep(Duration::from_millis(64));
+        if try_lock(file) {
+            return None;
+        }
+    }
+}
+
+/// Writes over the lock file in place, rather than by renaming a new one into place, so the
+/// flock stays on the file everyone else opens.
+fn write(file: &File, info: &LockInfo) -> io::Result<()> {
+    let json = serde_json::to_string(info).unwrap_or_log();
+    file.write_all_at(json.as_bytes(), 0)?;
+    file.set_len(json.len() as u64)
+}
+
+/// Takes the lock for this process, or returns the live process that holds it.
+#[instrument]
+pub fn acquire() -> Result<(), LockInfo> {
+    fs::create_dir_all(&*DATA_DIR).unwrap_or_log();
+    let file = open().unwrap_or_log();
+    loop {
+        if try_lock(&file) {
+            let info = LockInfo::current(Phase::Starting);
+            write(&file, &info).unwrap_or_log();
+            debug!("Took lock {:?}", &*LOCK_PATH);
+            *HELD.lock().unwrap_or_log() = Some(Held { file, info });
+            return Ok(());
+        }
+        if let Some(info) = holder(&file) {
+            return Err(info);
+        }
+    }
+}
+
+/// Like [`acquire`], but if the holder's game session is already over and it's only
+/// finishing its sync, waits for it to finish and then takes the lock.
+pub fn acquire_or_wait() -> Result<(), LockInfo> {
+    loop {
+        match acquire() {
+            Err(holder) if holder.phase.after_game() => {
+                info!(
+                    "Waiting for the previous session (PID {}, {}) to finish.",
+                    holder.pid, holder.phase
+                );
+                while LockInfo::live().is_some() {
+                    sleep(Duration::from_millis(1024));
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Records the current phase in the lock file, if this process holds it.
+pub fn set_phase(phase: Phase) {
+    let mut held = HELD.lock().unwrap_or_log();
+    let Some(held) = held.as_mut() else {
+        return;
+    };
+    debug!("Phase: {phase}");
+    held.info.phase = phase;
+    if let Err(err) = write(&held.file, &held.info) {
+        warn!("Couldn't record phase {phase} in {:?}: {err}", &*LOCK_PATH);
+    }
+}
+
+/// What this process has recorded in the lock file, if it holds the lock.
+pub fn info() -> Option<LockInfo> {
+    HELD.lock()
+        .unwrap_or_log()
+        .as_ref()
+        .map(|held| held.info.clone())
+}
+
+/// The phase recorded by this process, if it holds the lock.
+pub fn phase() -> Option<Phase> {
+    info().map(|info| info.phase)
+}
+
+/// Lets go of the lock. The file stays, emptied so nobody mistakes it for a record of a
+/// running process.
+pub fn release() {
+    if let Some(held) = HELD.lock().unwrap_or_log().take() {
+        held.file.set_len(0).ok();
+        debug!("Released lock {:?}", &*LOCK_PATH);
+    }
+}
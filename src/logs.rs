@@ -0,0 +1,268 @@
+//! Log files, rotated daily under `LOG_DIR`, and the `log` command for reading them back.
+
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tracing::info_span;
+use tracing::Span;
+use tracing_appender::rolling::RollingFileAppender;
+use tracing_appender::rolling::Rotation;
+use tracing_subscriber::prelude::*;
+use tracing_unwrap::ResultExt;
+
+use crate::config::Config;
+use crate::config::LogFormat;
+use crate::dirs::LOG_DIR;
+use crate::NAME;
+
+pub fn init() {
+    // Logging starts before anything else, so we don't create the repo just to read its config.
+    // Its warnings can't go to the log files before they're set up, so they go to stderr.
+    let config = tracing::subscriber::with_default(
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_max_level(tracing::Level::WARN)
+            .finish(),
+        Config::current,
+    );
+
+    // The appender only cleans up old files in a directory that's already there.
+    fs::create_dir_all(&*LOG_DIR).unwrap_or_log();
+
+    // Writes are blocking, since a background writer thread wouldn't survive daemonize()'s
+    // fork.
+    let file_appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(NAME)
+        .filename_suffix("log")
+        .max_log_files(config.log_retention)
+        .build(&*LOG_DIR)
+        .unwrap_or_log();
+
+    let (text, json) = match config.log_format {
+        LogFormat::Text => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(file_appender)
+                    .with_ansi(false),
+            ),
+            None,
+        ),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_writer(file_appender),
+            ),
+        ),
+    };
+
+    tracing_subscriber::registry()
+        .with(text)
+        .with(json)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_filter(tracing_subscriber::EnvFilter::from_default_env()),
+        )
+        .init();
+}
+
+/// A short ID, unique enough to tell one play session from another.
+pub fn session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let seed = format!("{nanos}:{}", std::process::id());
+    sha1_smol::Sha1::from(seed).digest().to_string()[..8].to_string()
+}
+
+/// A span for everything logged about one play session, tagged with its ID so the
+/// `log --last-session` command can filter on it.
+pub fn session_span(id: &str) -> Span {
+    info_span!(parent: None, "session", %id)
+}
+
+/// One event from a log file, in either format.
+struct Record {
+    level: String,
+    session: Option<String>,
+    text: String,
+}
+
+impl Record {
+    fn parse_text(text: String) -> Record {
+        let level = text
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+        let session = text.split_once("session{id=").and_then(|(_, rest)| {
+            let (id, _) = rest.split_once('}')?;
+            Some(id.to_string())
+        });
+        Record {
+            level,
+            session,
+            text,
+        }
+    }
+
+    fn parse_json(line: &str) -> Option<Record> {
+        let event: Value = serde_json::from_str(line).ok()?;
+        let level = event["level"].as_str().unwrap_or_default().to_string();
+        let spans = event["spans"].as_array().cloned().unwrap_or_default();
+        let session = spans
+            .iter()
+            .find(|span| span["name"] == "session")
+            .and_then(|span| span["id"].as_str())
+            .map(str::to_string);
+
+        let mut text = format!(
+            "{} {level:>5} ",
+            event["timestamp"].as_str().unwrap_or_default()
+        );
+        for span in &spans {
+            text.push_str(span["name"].as_str().unwrap_or_default());
+            let fields = span
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(key, _)| *key != "name")
+                .map(|(key, value)| format!("{key}={}", plain(value)))
+                .collect::<Vec<_>>();
+            if !fields.is_empty() {
+                text.push_str(&format!("{{{}}}", fields.join(" ")));
+            }
+            text.push_str(": ");
+        }
+        text.push_str(event["target"].as_str().unwrap_or_default());
+        text.push_str(": ");
+        let fields = event["fields"].as_object().cloned().unwrap_or_default();
+        if let Some(message) = fields.get("message") {
+            text.push_str(&plain(message));
+        }
+        for (key, value) in fields.iter().filter(|(key, _)| *key != "message") {
+            text.push_str(&format!(" {key}={}", plain(value)));
+        }
+
+        Some(Record {
+            level,
+            session,
+            text,
+        })
+    }
+
+    fn is_error(&self) -> bool {
+        matches!(self.level.as_str(), "ERROR" | "WARN")
+    }
+}
+
+/// A JSON value as it would have been written to a text log.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The log files, oldest first.
+fn log_files() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(&*LOG_DIR) else {
+        return Vec::new();
+    };
+    let mut files = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with(NAME) && name.ends_with(".log")
+        })
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            (modified, entry.path())
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+fn read_records() -> Vec<Record> {
+    let mut records = Vec::new();
+    for path in log_files() {
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        // Text events can span several lines, e.g. when they include a pretty-printed value,
+        // so any line that doesn't start with a timestamp continues the previous event.
+        let mut text: Option<String> = None;
+        for line in contents.lines() {
+            if line.starts_with('{') {
+                records.extend(text.take().map(Record::parse_text));
+                records.extend(Record::parse_json(line));
+            } else if starts_with_timestamp(line) {
+                records.extend(text.take().map(Record::parse_text));
+                text = Some(line.to_string());
+            } else if let Some(text) = &mut text {
+                text.push('\n');
+                text.push_str(line);
+            }
+        }
+        records.extend(text.take().map(Record::parse_text));
+    }
+    records
+}
+
+fn starts_with_timestamp(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    bytes.len() > 10
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[10] == b'T'
+}
+
+/// `log [--last-session] [--errors]` prints the logs, optionally only those of the most recent
+/// session and only warnings and errors.
+pub fn log_command(args: &[&str]) {
+    let mut last_session = false;
+    let mut errors = false;
+    for arg in args {
+        match *arg {
+            "--last-session" => last_session = true,
+            "--errors" => errors = true,
+            other => {
+                eprintln!("Unknown option {other:?}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let records = read_records();
+    let session = if last_session {
+        let session = records
+            .iter()
+            .rev()
+            .find_map(|record| record.session.clone());
+        if session.is_none() {
+            println!("No sessions found in {:?}.", &*LOG_DIR);
+            return;
+        }
+        session
+    } else {
+        None
+    };
+
+    for record in records {
+        if session.is_some() && record.session != session {
+            continue;
+        }
+        if errors && !record.is_error() {
+            continue;
+        }
+        println!("{}", record.text);
+    }
+}
@@ -1,177 +1,252 @@
 #![allow(dead_code)]
 use dirs::GIT_DIR;
-use git2::BranchType;
 use git2::Repository;
 use itertools::Itertools;
 use std::collections::BTreeMap;
-use std::fs;
-use std::io::Write;
-use std::os::unix::prelude::PermissionsExt;
+use std::path::Path;
 use std::path::PathBuf;
+use std::process::exit;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
 use tracing::info;
 use tracing::trace;
-use tracing_subscriber::prelude::*;
 
+mod binary_vdf;
+mod categories;
+mod celeste_settings;
 mod celeste_stats;
+mod charts;
+mod compare;
+mod config;
+mod control;
 mod dirs;
 mod domutils;
 mod durationutils;
+mod game_stats;
+mod goals;
+mod goldens;
+mod history;
 mod install;
+mod leaderboard;
+mod livesplit;
+mod lock;
+mod logs;
+mod notify;
+mod sessions;
+mod settle;
 mod steam_app;
+mod steam_cloud;
+mod steam_shortcut;
+mod steam_user;
 mod stringutils;
-use home::home_dir;
-use once_cell::sync::Lazy;
+mod sync;
+#[cfg(test)]
+mod testutils;
+mod watch;
 use tracing_unwrap::OptionExt;
 use tracing_unwrap::ResultExt;
 mod daemon;
-use crate::celeste_stats::celeste_stats;
 use crate::daemon::*;
+use crate::lock::LockInfo;
+use crate::lock::Phase;
+use crate::sessions::Launch;
+use crate::steam_user::SteamUser;
+use crate::sync::SyncSession;
 
-use crate::dirs::LOG_DIR;
 use crate::steam_app::CELESTE;
 
 fn main() {
-    // This is blocking and probably slow, but the easiest alternatives didn't work once
-    // we had forked daemon threads going.
-    let file_appender =
-        tracing_appender::rolling::never(LOG_DIR.clone(), NAME.to_string() + ".log");
-
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_writer(file_appender)
-                .with_ansi(false),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_filter(tracing_subscriber::EnvFilter::from_default_env()),
-        )
-        .init();
+    logs::init();
 
     trace!("env = {:#?}", std::env::vars().collect::<BTreeMap<_, _>>());
     trace!("argv = {:#?}", std::env::args().collect::<Vec<_>>());
 
     let argv = std::env::args().collect_vec();
-    if &argv[1..] == &["install"] {
-        crate::install::install();
-        return;
+    match argv[1..].iter().map(String::as_str).collect_vec()[..] {
+        ["install"] => {
+            crate::install::install();
+            return;
+        }
+        ["uninstall"] => {
+            crate::install::uninstall(false);
+            return;
+        }
+        ["uninstall", "--purge"] => {
+            crate::install::uninstall(true);
+            return;
+        }
+        [command @ ("status" | "sync-now" | "stop")] => {
+            if !crate::control::request(command) {
+                exit(1);
+            }
+            return;
+        }
+        ["watch"] => crate::watch::watch(),
+        ["log", ref args @ ..] => {
+            crate::logs::log_command(args);
+            return;
+        }
+        ["render", ref args @ ..] => {
+            crate::game_stats::render_command(args);
+            return;
+        }
+        ["compare", ref args @ ..] => {
+            crate::compare::compare_command(args);
+            return;
+        }
+        ["goldens", ref args @ ..] => {
+            crate::goldens::goldens_command(args);
+            return;
+        }
+        ["leaderboard", ref args @ ..] => {
+            crate::leaderboard::leaderboard_command(args);
+            return;
+        }
+        ["livesplit", ref args @ ..] => {
+            crate::livesplit::livesplit_command(args);
+            return;
+        }
+        _ => {}
     }
 
     let steam = SteamEnv::get();
 
-    if let Some(steam) = steam {
+    // Steam replaces `%command%` in a game's launch options with the game's own command line,
+    // which always starts with an absolute path.
+    let wrapped = argv[1..].to_vec();
+    if wrapped.first().map(|arg| Path::new(arg).is_absolute()) == Some(true) {
+        info!("Wrapping game command {wrapped:?}");
+
+        // If we were launched through our own shortcut, that process is already syncing
+        // this session, and we only need to run the game.
+        let session = match lock::acquire() {
+            Ok(()) => {
+                let user = steam
+                    .and_then(|steam| steam.user)
+                    .or_else(SteamUser::most_recent);
+                let session = Arc::new(Mutex::new(SyncSession::new(user)));
+                control::set_session(Some(session.clone()));
+                control::serve(false);
+                Some(session)
+            }
+            Err(holder) => {
+                info!(
+                    "PID {} is already syncing ({}); not syncing.",
+                    holder.pid, holder.phase
+                );
+                None
+            }
+        };
+
+        let mut child = Command::new(&wrapped[0])
+            .args(&wrapped[1..])
+            .spawn()
+            .unwrap_or_log();
+
+        if let Some(session) = &session {
+            session.lock().unwrap_or_log().game_started(Launch::Wrapped);
+            lock::set_phase(Phase::Fetching);
+            session.lock().unwrap_or_log().fetch();
+        }
+
+        let _span = session
+            .as_ref()
+            .map(|session| session.lock().unwrap_or_log().span.clone().entered());
+        lock::set_phase(Phase::Watching);
+        info!("Waiting for Celeste to exit");
+        let status = child.wait().unwrap_or_log();
+        info!("Celeste has exited with {status}.");
+
+        if let Some(session) = session {
+            let mut session = session.lock().unwrap_or_log();
+            session.game_exited();
+            if !control::stopped() {
+                session.finish();
+            }
+        }
+        control::close();
+        lock::release();
+        exit(status.code().unwrap_or(1));
+    } else if !wrapped.is_empty() {
+        eprintln!("Unknown command {wrapped:?}");
+        exit(2);
+    }
+
+    let already_running = |holder: LockInfo| {
+        let message = format!(
+            "{NAME} is already running as PID {} ({}).",
+            holder.pid, holder.phase
+        );
+        info!("{message}");
+        eprintln!("{message}");
+    };
+
+    let user = if let Some(steam) = steam {
+        // We check here so the message reaches Steam's console, but only take the lock once
+        // we've forked, so the lock names the process that stays around.
+        if let Some(holder) = LockInfo::live().filter(|holder| !holder.phase.after_game()) {
+            already_running(holder);
+            return;
+        }
         info!("Steam environment detected: {steam:#?}. Daemonizing.");
         daemonize();
+        if let Err(holder) = lock::acquire_or_wait() {
+            already_running(holder);
+            return;
+        }
+        steam.user
     } else {
         info!("Not in Steam environment");
-    }
-
-    let repo = git_repo();
+        if let Err(holder) = lock::acquire_or_wait() {
+            already_running(holder);
+            return;
+        }
+        SteamUser::most_recent()
+    };
 
-    if let Ok(mut _origin) = repo.find_remote("origin") {
-        info!("Pulling changes from remote origin");
-        let mut cmd = std::process::Command::new("git");
-        cmd.arg("fetch");
-        cmd.arg("--verbose");
-        cmd.arg("origin");
-        cmd.env("GIT_DIR", &*GIT_DIR);
-        cmd.status().unwrap_or_log();
-    } else {
-        trace!("No origin remote found, not pulling");
-    }
+    let session = Arc::new(Mutex::new(SyncSession::new(user)));
+    control::set_session(Some(session.clone()));
+    control::serve(true);
+    let _span = session.lock().unwrap_or_log().span.clone().entered();
 
-    // XXX: Celeste will use existing save files from the disk, if no save file exists in that
-    // slot in the cloud. If we want to prevent this, we can delete the existing save files
-    // before launching Celeste with Steam. If we wanted to be ambitious we could check Steam's
-    // local cache metadata (it's a vdf file somewhere) to find out what files already match
-    // this account's known cloud save.
+    let start = Instant::now();
+    lock::set_phase(Phase::Fetching);
+    session.lock().unwrap_or_log().fetch();
+    info!("Fetched in {:?}", start.elapsed());
 
     info!("Launching Celeste");
-
-    let celeste = CELESTE.launch();
+    lock::set_phase(Phase::Launching);
+
+    let start = Instant::now();
+    let launch_timeout = session.lock().unwrap_or_log().config.launch_timeout;
+    let launch = match CELESTE.find_process() {
+        Some(_) => Launch::AlreadyRunning,
+        None => Launch::Launched,
+    };
+    let Some(celeste) = CELESTE.launch(launch_timeout) else {
+        control::close();
+        lock::release();
+        exit(0);
+    };
+    info!("Launched in {:?}", start.elapsed());
+    session.lock().unwrap_or_log().game_started(launch);
 
     info!("Waiting for Celeste to exit");
+    lock::set_phase(Phase::Watching);
 
     celeste.wait_for_exit();
+    session.lock().unwrap_or_log().game_exited();
 
-    info!("Celeste has exited. Reading save files.");
+    info!("Celeste has exited.");
 
-    let mut files = BTreeMap::new();
+    let start = Instant::now();
+    session.lock().unwrap_or_log().finish();
+    info!("Synced in {:?}", start.elapsed());
 
-    for entry in CELESTE.saves_dir().read_dir().unwrap_or_log() {
-        let entry = entry.unwrap_or_log();
-        let path = entry.path();
-        if path.extension().map(|s| s == "celeste").unwrap_or(false) {
-            let contents = std::fs::read_to_string(path).unwrap_or_log();
-            files.insert(entry.file_name(), contents);
-        }
-    }
-
-    let mut generated = BTreeMap::new();
-    for (name, body) in files.iter() {
-        let name = name.to_str().unwrap_or_log();
-        if name == "settings.celeste" {
-            continue;
-        }
-        let stats = celeste_stats(&body);
-        generated.insert(name.replace(".celeste", ".html"), stats);
-    }
-
-    for (name, stats) in generated.into_iter() {
-        files.insert(name.into(), stats);
-    }
-
-    let mut tree = repo.treebuilder(None).unwrap_or_log();
-    for (name, body) in files.iter() {
-        let mut blob = repo.blob_writer(Some(name.as_ref())).unwrap_or_log();
-        blob.write_all(body.as_bytes()).unwrap_or_log();
-        let blob = blob.commit().unwrap_or_log();
-        tree.insert(name, blob, 0o100_644).unwrap_or_log();
-    }
-    let tree = tree.write().unwrap_or_log();
-    let tree = repo.find_tree(tree).unwrap_or_log();
-
-    let branch = repo.find_branch("celeste", BranchType::Local).ok();
-    let existing_tree = branch
-        .as_ref()
-        .map(|b| b.get().peel_to_tree().unwrap_or_log());
-
-    if Some(tree.id()) == existing_tree.map(|t| t.id()) {
-        info!("No changes to save.");
-    } else {
-        // TODO: if upstream doesn't match, add both as parents?
-        let parents = branch
-            .map(|b| vec![b.get().peel_to_commit().unwrap_or_log()])
-            .unwrap_or_default();
-
-        let signature = repo.signature().unwrap_or_log();
-        let commit = repo
-            .commit(
-                Some("refs/heads/celeste"),
-                &signature,
-                &signature,
-                &tree.id().to_string(),
-                &tree,
-                parents[..].iter().collect_vec().as_ref(),
-            )
-            .unwrap_or_log();
-
-        info!("Committed {commit:?} to git branch 'celeste'");
-
-        if let Ok(mut _origin) = repo.find_remote("origin") {
-            info!("Pushing changes to remote 'origin");
-            // We shell out instead of figuring out the auth dance.
-            let mut cmd = std::process::Command::new("git");
-            cmd.arg("push");
-            cmd.arg("--verbose");
-            cmd.arg("origin");
-            cmd.arg("celeste:celeste");
-            cmd.env("GIT_DIR", &*GIT_DIR);
-            cmd.status().unwrap_or_log();
-        } else {
-            trace!("No origin remote found, not pushing");
-        }
-    }
+    control::close();
+    lock::release();
 }
 
 pub fn git_repo() -> Repository {
@@ -190,6 +265,7 @@ pub fn git_repo() -> Repository {
 struct SteamEnv {
     steam_exe: PathBuf,
     username: String,
+    user: Option<SteamUser>,
 }
 
 impl SteamEnv {
@@ -202,11 +278,14 @@ impl SteamEnv {
 
         let steam_exe = env.get("STEAMSCRIPT").unwrap_or_log().into();
 
-        let username = env.get("SteamUser").unwrap_or_log().into();
+        let username: String = env.get("SteamUser").unwrap_or_log().into();
+
+        let user = SteamUser::by_account_name(&username);
 
         Some(SteamEnv {
             steam_exe,
             username,
+            user,
         })
     }
 }
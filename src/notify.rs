@@ -0,0 +1,192 @@
+//! Desktop notifications through the freedesktop notification service on the D-Bus session
+//! bus. The bus comes from `DBUS_SESSION_BUS_ADDRESS`, so pointing that at a private
+//! `dbus-daemon` with a stand-in `org.freedesktop.Notifications` service is enough to see
+//! exactly what would be sent.
+
+use std::collections::HashMap;
+use tracing::debug;
+use tracing::instrument;
+use tracing::warn;
+use zbus::zvariant::Value;
+
+use crate::dirs::ETC_DIR;
+use crate::NAME;
+
+/// The notification's urgency hint, from the freedesktop notification spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Low = 0,
+    Normal = 1,
+    Critical = 2,
+}
+
+/// Shows a notification, logging rather than failing if there's no notification service.
+#[instrument]
+pub fn send(summary: &str, body: &str, urgency: Urgency) {
+    match try_send(summary, body, urgency) {
+        Ok(id) => debug!("Sent notification {id}"),
+        Err(err) => warn!("Couldn't send notification: {err}"),
+    }
+}
+
+fn try_send(summary: &str, body: &str, urgency: Urgency) -> zbus::Result<u32> {
+    send_on(
+        &zbus::blocking::Connection::session()?,
+        summary,
+        body,
+        urgency,
+    )
+}
+
+fn send_on(
+    connection: &zbus::blocking::Connection,
+    summary: &str,
+    body: &str,
+    urgency: Urgency,
+) -> zbus::Result<u32> {
+    let icon = ETC_DIR.join("0_icon.png");
+    let icon = if icon.exists() {
+        icon.to_string_lossy().into_owned()
+    } else {
+        String::new()
+    };
+
+    let mut hints = HashMap::new();
+    hints.insert("urgency", Value::U8(urgency as u8));
+
+    let reply = connection.call_method(
+        Some("org.freedesktop.Notifications"),
+        "/org/freedesktop/Notifications",
+        Some("org.freedesktop.Notifications"),
+        "Notify",
+        &(
+            NAME,
+            0u32,
+            icon.as_str(),
+            summary,
+            body,
+            Vec::<&str>::new(),
+            hints,
+            -1i32,
+        ),
+    )?;
+    reply.body()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::scratch_dir;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::process::Child;
+    use std::process::Command;
+    use std::process::Stdio;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use zbus::zvariant::OwnedValue;
+
+    type Sent = Arc<Mutex<Vec<(String, String, String, Option<u8>)>>>;
+
+    /// A stand-in notification service that keeps what it's sent.
+    struct Notifications {
+        sent: Sent,
+    }
+
+    #[zbus::dbus_interface(name = "org.freedesktop.Notifications")]
+    impl Notifications {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = hints
+                .get("urgency")
+                .and_then(|urgency| u8::try_from(urgency).ok());
+            let mut sent = self.sent.lock().unwrap();
+            sent.push((app_name, summary, body, urgency));
+            sent.len() as u32
+        }
+    }
+
+    /// Stops the test's bus however the test ends.
+    struct Daemon(Child);
+
+    impl Drop for Daemon {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    fn sends_to_the_notification_service() {
+        let dir = scratch_dir("notify");
+        let config = dir.join("bus.conf");
+        std::fs::write(
+            &config,
+            format!(
+                r#"<busconfig>
+  <type>session</type>
+  <listen>unix:path={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#,
+                dir.join("bus").display()
+            ),
+        )
+        .unwrap();
+        let mut daemon = Daemon(
+            Command::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .args(["--print-address", "--nofork"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("this test needs dbus-daemon on the PATH"),
+        );
+        let mut address = String::new();
+        BufReader::new(daemon.0.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+
+        let sent = Sent::default();
+        let _service = zbus::blocking::ConnectionBuilder::address(address.trim())
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/Notifications",
+                Notifications { sent: sent.clone() },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let connection = zbus::blocking::ConnectionBuilder::address(address.trim())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let id = send_on(&connection, "New PB", "1A in 2:03.456", Urgency::Critical);
+        assert_eq!(id.unwrap(), 1);
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [(
+                NAME.to_string(),
+                "New PB".to_string(),
+                "1A in 2:03.456".to_string(),
+                Some(2)
+            )]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! A record of each play session, appended to `sessions.jsonl` in the committed tree so play
+//! frequency and session length can be charted from the history.
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tracing_unwrap::ResultExt;
+
+use crate::steam_user::SteamUser;
+
+pub const SESSIONS_FILE: &str = "sessions.jsonl";
+
+/// How the game came to be running for a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Launch {
+    /// We asked Steam to start it.
+    Launched,
+    /// It was already running when we went to start it.
+    AlreadyRunning,
+    /// Steam started it with us wrapping its command, through its launch options.
+    Wrapped,
+    /// The watcher noticed it had been started some other way.
+    Noticed,
+}
+
+/// One line of `sessions.jsonl`. The commit that adds the line is the post-session commit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// The ID tagging the session's log lines.
+    pub id: String,
+    /// When the game started and exited, in seconds since the Unix epoch.
+    pub started: u64,
+    pub ended: u64,
+    pub duration_secs: u64,
+    pub launch: Launch,
+    pub steam_id: Option<u64>,
+    pub persona_name: Option<String>,
+    pub machine: String,
+    /// The branch's commit before the session, if it had one.
+    pub before: Option<String>,
+}
+
+impl SessionRecord {
+    pub fn new(
+        id: &str,
+        started: SystemTime,
+        ended: SystemTime,
+        launch: Launch,
+        user: Option<&SteamUser>,
+        before: Option<String>,
+    ) -> Self {
+        let started = unix_seconds(started);
+        let ended = unix_seconds(ended);
+        SessionRecord {
+            id: id.to_string(),
+            started,
+            ended,
+            duration_secs: ended.saturating_sub(started),
+            launch,
+            steam_id: user.map(|user| u64::from(user.steam_id)),
+            persona_name: user.map(|user| user.persona_name.clone()),
+            machine: hostname(),
+            before,
+        }
+    }
+
+    /// `existing` with this record appended as another line.
+    pub fn append_to(&self, existing: Option<&str>) -> String {
+        let mut sessions = existing.unwrap_or_default().to_string();
+        if !sessions.is_empty() && !sessions.ends_with('\n') {
+            sessions.push('\n');
+        }
+        sessions.push_str(&serde_json::to_string(self).unwrap_or_log());
+        sessions.push('\n');
+        sessions
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
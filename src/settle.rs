@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use tracing::debug;
+use tracing::info;
+use tracing::instrument;
+use tracing::warn;
+
+use crate::steam_app::SteamApp;
+use crate::steam_cloud::CloudReport;
+use crate::steam_cloud::CloudStatus;
+
+/// The contents of every save file, read while none of them were changing. Not every file is
+/// text: Everest mods may save theirs in a binary format.
+pub type Snapshot = BTreeMap<OsString, Vec<u8>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+    contents: Vec<u8>,
+}
+
+/// Waits until the app's save files have stopped changing, each XML one parses completely, and
+/// Steam has caught the cloud up with them, then returns their contents. Gives up waiting
+/// after `timeout` and returns whatever was last read.
+///
+/// Steam only updates its cloud cache while it's uploading, so a file it hasn't caught up
+/// with over a whole poll isn't waited on any longer: cloud sync may be off for the game or
+/// the account.
+#[instrument]
+pub fn settled_saves(app: &SteamApp, account_id: Option<u32>, timeout: Duration) -> Snapshot {
+    let start = Instant::now();
+    let saves_dir = app.saves_dir();
+
+    let cloud = |account_id| CloudReport::get(app, Some(account_id)).files;
+    let mut previous = read_saves(&saves_dir);
+    let mut previous_cloud = account_id.map(cloud);
+    loop {
+        sleep(Duration::from_millis(1024));
+        let current = read_saves(&saves_dir);
+
+        let stable = current == previous;
+        let complete = current.values().all(|state| is_complete(&state.contents));
+        let current_cloud = account_id.map(cloud);
+        let uploading = current_cloud.as_ref().is_some_and(|files| {
+            files
+                .values()
+                .any(|status| *status == CloudStatus::LocalNewer)
+                && current_cloud != previous_cloud
+        });
+        debug!("stable = {stable}, complete = {complete}, uploading = {uploading}");
+
+        if stable && complete && !uploading {
+            info!("Save files settled after {:?}", start.elapsed());
+            return snapshot(current);
+        }
+        if start.elapsed() > timeout {
+            warn!(
+                "Save files didn't settle within {timeout:?} (stable = {stable}, \
+                 complete = {complete}, uploading = {uploading}); using them anyway"
+            );
+            return snapshot(current);
+        }
+        previous = current;
+        previous_cloud = current_cloud;
+    }
+}
+
+fn read_saves(saves_dir: &Path) -> BTreeMap<OsString, FileState> {
+    let mut files = BTreeMap::new();
+    let Ok(entries) = saves_dir.read_dir() else {
+        return files;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if !path.extension().map(|s| s == "celeste").unwrap_or(false) {
+            continue;
+        }
+        // A file can vanish or be replaced between listing and reading; the next read will
+        // differ from this one and we'll try again.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let Ok(contents) = std::fs::read(&path) else {
+            continue;
+        };
+        files.insert(
+            entry.file_name(),
+            FileState {
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+                contents,
+            },
+        );
+    }
+    files
+}
+
+/// Whether a save file has been written out completely. We can only tell for the game's own
+/// XML saves; Everest's mod saves may be YAML or binary, and only have to stop changing.
+fn is_complete(contents: &[u8]) -> bool {
+    match std::str::from_utf8(contents) {
+        Ok(text)
+            if text
+                .trim_start_matches('\u{feff}')
+                .trim_start()
+                .starts_with('<') =>
+        {
+            text.parse::<minidom::Element>().is_ok()
+        }
+        _ => true,
+    }
+}
+
+fn snapshot(files: BTreeMap<OsString, FileState>) -> Snapshot {
+    files
+        .into_iter()
+        .map(|(name, state)| (name, state.contents))
+        .collect()
+}
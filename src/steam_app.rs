@@ -1,16 +1,21 @@
 #![allow(non_upper_case_globals)]
 
 use keyvalues_parser::Vdf;
-use procfs;
 use smartstring::alias::String as SmartString;
 use std::collections::BTreeMap;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
 use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
-use std::process::exit;
 use std::process::Command;
+use std::process::Stdio;
 use std::str::FromStr;
 use std::thread::sleep;
 use std::time::Duration;
+use std::time::Instant;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
@@ -22,8 +27,6 @@ use tracing_unwrap::ResultExt;
 use home::home_dir;
 use once_cell::sync::Lazy;
 
-use crate::daemon::forked_daemon;
-
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct SteamApp {
     pub id: u32,
@@ -52,6 +55,23 @@ pub struct AppProcess {
     pub stat: procfs::process::Stat,
     pub cwd: PathBuf,
     pub exe: PathBuf,
+    pub cmdline: Vec<String>,
+    /// Clock ticks after boot at which the process started, so we can tell if its PID has
+    /// been reused.
+    pub start_time: u64,
+    pub matched_by: ProcessMatch,
+}
+
+/// How a process was identified as an app's, from most to least certain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProcessMatch {
+    /// The executable is inside the app's install directory.
+    Exe,
+    /// The command line runs a file from the install directory, such as a launch script or
+    /// the Windows build under Proton.
+    Cmdline,
+    /// Steam launched it for the app, and it's named like the app.
+    SteamAppId,
 }
 
 impl SteamApp {
@@ -64,34 +84,58 @@ impl SteamApp {
         self.id
     }
 
+    /// Launches the app through Steam, unless it's already running, and waits for its
+    /// process to appear. `timeout` starts counting once Steam itself is up, since a cold
+    /// Steam start can take longer than the game does.
     #[instrument]
-    pub fn launch(&self) -> AppProcess {
+    pub fn launch(&self, timeout: Duration) -> Option<AppProcess> {
         if let Some(process) = self.find_process() {
-            return process;
+            return Some(process);
         }
 
+        let steam_was_running = steam_is_running();
+
         let mut command = Command::new("steam");
         command.arg(format!("steam://rungameid/{}", self.id()));
 
         info!("Launching {:?} as {:?}", self.name, &command);
 
-        if forked_daemon() {
-            let error = command.exec();
-            error!("{error:#?}");
-            exit(1)
+        // We don't fork and exec here because forking is unsafe once the control socket's
+        // thread is running. Steam gets its own process group so it isn't tied to ours.
+        command.process_group(0);
+        command.stdin(Stdio::null());
+        match command.spawn() {
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(error) => {
+                error!("{error:#?}");
+                return None;
+            }
         }
 
         debug!("Waiting for {:?} to start...", self.name);
 
-        let start = std::time::Instant::now();
+        let start = Instant::now();
+        let mut steam_started = steam_was_running.then_some(start);
         loop {
             if let Some(process) = self.find_process() {
-                return process;
+                info!("{:?} started after {:?}", self.name, start.elapsed());
+                return Some(process);
+            }
+
+            if steam_started.is_none() && steam_is_running() {
+                info!("Steam started after {:?}", start.elapsed());
+                steam_started = Some(Instant::now());
             }
-            let elapsed = start.elapsed();
-            if elapsed > Duration::from_secs(24) {
-                error!("No {:?} process after {:?}", self.name, elapsed);
-                exit(0);
+
+            let waited = match steam_started {
+                Some(steam_started) => steam_started.elapsed(),
+                None => start.elapsed().saturating_sub(STEAM_STARTUP_TIMEOUT),
+            };
+            if waited > timeout {
+                error!("No {:?} process after {:?}", self.name, start.elapsed());
+                return None;
             }
             sleep(Duration::from_millis(1024));
         }
@@ -100,16 +144,66 @@ impl SteamApp {
     #[instrument]
     pub fn find_process(&self) -> Option<AppProcess> {
         trace!("Checking for process...");
-        for process in procfs::process::all_processes().unwrap_or_log() {
-            let process = process.unwrap_or_log();
-            if process.cwd().unwrap_or_default() == self.app_dir() {
-                return Some(AppProcess {
+        let own_pid = std::process::id() as i32;
+        procfs::process::all_processes()
+            .unwrap_or_log()
+            .filter_map(Result::ok)
+            .filter(|process| process.pid() != own_pid)
+            .filter_map(|process| {
+                let matched_by = self.process_match(&process)?;
+                let stat = process.stat().ok()?;
+                Some(AppProcess {
                     app: self.clone(),
-                    stat: process.stat().unwrap_or_log(),
-                    cwd: process.cwd().unwrap_or_log(),
-                    exe: process.exe().unwrap_or_log(),
+                    start_time: stat.starttime,
+                    stat,
+                    cwd: process.cwd().unwrap_or_default(),
+                    exe: process.exe().unwrap_or_default(),
+                    cmdline: process.cmdline().unwrap_or_default(),
+                    matched_by,
                     process,
-                });
+                })
+            })
+            .min_by_key(|process| (process.matched_by, process.start_time))
+    }
+
+    /// Whether the process `pid` looks like this app, without looking at any others.
+    pub fn is_process(&self, pid: i32) -> bool {
+        procfs::process::Process::new(pid)
+            .ok()
+            .and_then(|process| self.process_match(&process))
+            .is_some()
+    }
+
+    /// Whether `process` looks like this app. We don't go by the working directory, which
+    /// Everest and Olympus launches don't share, and which any shell could be sitting in.
+    fn process_match(&self, process: &procfs::process::Process) -> Option<ProcessMatch> {
+        let app_dir = self.app_dir();
+
+        if let Ok(exe) = process.exe() {
+            if exe.starts_with(&app_dir) {
+                return Some(ProcessMatch::Exe);
+            }
+        }
+
+        let cmdline = process.cmdline().unwrap_or_default();
+        // Only the program and its interpreter's first argument count, so an editor with a
+        // game file open doesn't match.
+        for arg in cmdline.iter().take(2) {
+            let unix_path = arg.replace('\\', "/");
+            let file_name = unix_path.rsplit('/').next().unwrap_or_default();
+            if PathBuf::from(arg).starts_with(&app_dir)
+                || file_name.eq_ignore_ascii_case(&format!("{}.exe", self.path))
+            {
+                return Some(ProcessMatch::Cmdline);
+            }
+        }
+
+        let comm = process.stat().ok()?.comm;
+        if comm.to_lowercase().starts_with(&self.path.to_lowercase()) {
+            let environ = process.environ().ok()?;
+            let app_id = environ.get(std::ffi::OsStr::new("SteamAppId"))?;
+            if app_id.to_str() == Some(&self.id.to_string()) {
+                return Some(ProcessMatch::SteamAppId);
             }
         }
 
@@ -140,19 +234,84 @@ impl SteamApp {
 impl AppProcess {
     #[instrument]
     pub fn still_alive(&self) -> bool {
-        // if we can't read this file, we assume the process is terminated.
-        self.process.stat().is_ok()
+        // if we can't read this file, we assume the process is terminated. If it has a
+        // different start time, the PID now belongs to another process.
+        match procfs::process::Process::new(self.process.pid()).and_then(|p| p.stat()) {
+            Ok(stat) => stat.starttime == self.start_time && stat.state != 'Z',
+            Err(_) => false,
+        }
     }
 
     #[instrument]
     pub fn wait_for_exit(&self) {
         debug!("Waiting for {:?} to exit...", self.app.name);
-        while self.still_alive() {
-            sleep(Duration::from_millis(1024));
+        let start = Instant::now();
+        if !self.wait_for_exit_pidfd() {
+            debug!("Falling back to polling");
+            while self.still_alive() {
+                sleep(Duration::from_millis(1024));
+            }
+        }
+        info!("{:?} exited after {:?}", self.app.name, start.elapsed());
+    }
+
+    /// Blocks until the process exits using a pidfd, which becomes readable when the process
+    /// terminates. Returns false if pidfds aren't available, so the caller can poll instead.
+    fn wait_for_exit_pidfd(&self) -> bool {
+        // SAFETY: pidfd_open(2) takes a PID and flags by value and reads no memory of ours;
+        // a stale or reused PID is an error or the wrong process, not undefined behaviour.
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, self.process.pid(), 0) };
+        if fd < 0 {
+            debug!("pidfd_open failed: {}", io::Error::last_os_error());
+            return false;
+        }
+        // SAFETY: the fd was just returned by a successful pidfd_open, so it's open and
+        // nothing else owns it; the `OwnedFd` is its only owner and closes it on drop.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        // The PID could have been reused before we opened the pidfd, but once it's open it
+        // can't be.
+        if !self.still_alive() {
+            return true;
+        }
+
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            // SAFETY: `pollfd` is a single valid, exclusively borrowed `pollfd`, matching the
+            // count of 1, and its fd is kept open by `fd` for the whole call.
+            match unsafe { libc::poll(&mut pollfd, 1, -1) } {
+                n if n > 0 => return true,
+                _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                _ => {
+                    debug!("poll on pidfd failed: {}", io::Error::last_os_error());
+                    return false;
+                }
+            }
         }
     }
 }
 
+/// How long Steam gets to start, when it wasn't already running, before the launch timeout
+/// starts counting anyway.
+const STEAM_STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Whether the Steam client itself is running.
+pub fn steam_is_running() -> bool {
+    procfs::process::all_processes()
+        .unwrap_or_log()
+        .filter_map(Result::ok)
+        .any(|process| {
+            process
+                .stat()
+                .map(|stat| stat.comm == "steam")
+                .unwrap_or(false)
+        })
+}
+
 pub static STEAM_APPS_DIR: Lazy<PathBuf> = Lazy::new(|| {
     let mut path = home_dir().unwrap_or_log();
     path.push(".local");
@@ -184,9 +343,8 @@ pub static ALL_APPS: Lazy<BTreeMap<u32, SteamApp>> = Lazy::new(|| {
         let manifest = manifest.get_obj().unwrap_or_log();
         let path = manifest
             .get("installdir")
-            .clone()
             .unwrap_or_log()
-            .get(0)
+            .first()
             .unwrap_or_log()
             .get_str()
             .unwrap_or_log()
@@ -194,9 +352,8 @@ pub static ALL_APPS: Lazy<BTreeMap<u32, SteamApp>> = Lazy::new(|| {
             .into();
         let name = manifest
             .get("name")
-            .clone()
             .unwrap_or_log()
-            .get(0)
+            .first()
             .unwrap_or_log()
             .get_str()
             .unwrap_or_log()
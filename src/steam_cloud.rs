@@ -0,0 +1,367 @@
+use keyvalues_parser::Obj;
+use keyvalues_parser::Vdf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use tracing::debug;
+use tracing::instrument;
+use tracing::trace;
+use tracing::warn;
+
+use crate::dirs::STEAM_USER_DATA_DIR;
+use crate::steam_app::SteamApp;
+
+/// Steam's local metadata about an app's cloud files, from
+/// `userdata/<account>/<app>/remotecache.vdf`.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteCache {
+    pub change_number: Option<u64>,
+    pub files: BTreeMap<String, RemoteFile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteFile {
+    pub size: u64,
+    pub sha: String,
+    /// Steam's `syncstate`, which is [`SYNCED`] once the file has been uploaded.
+    pub sync_state: u32,
+    pub local_time: u64,
+    pub remote_time: u64,
+}
+
+/// The `syncstate` of a file whose cached hash and size are what's in the cloud. Other states
+/// mean Steam has seen the local file but not finished uploading it.
+pub const SYNCED: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CloudStatus {
+    /// The local file matches the cloud copy.
+    InSync,
+    /// The local file has never been synced with this account's cloud.
+    LocalOnly,
+    /// The local file differs from the cloud copy and was written after it.
+    LocalNewer,
+    /// The cloud copy differs from the local file and was written after it.
+    CloudNewer,
+    /// The cloud has a file that doesn't exist locally.
+    CloudOnly,
+}
+
+impl fmt::Display for CloudStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CloudStatus::InSync => "in sync",
+            CloudStatus::LocalOnly => "local only",
+            CloudStatus::LocalNewer => "local newer",
+            CloudStatus::CloudNewer => "cloud newer",
+            CloudStatus::CloudOnly => "cloud only",
+        })
+    }
+}
+
+/// The cloud status of every save file, keyed by file name.
+#[derive(Debug, Clone, Default)]
+pub struct CloudReport {
+    pub account_id: Option<u32>,
+    pub files: BTreeMap<String, CloudStatus>,
+}
+
+impl CloudReport {
+    /// Compares the local saves of `app` against the cloud cache of `account_id`, or of the
+    /// account that most recently synced it.
+    pub fn get(app: &SteamApp, account_id: Option<u32>) -> Self {
+        let account_id = account_id.or_else(|| RemoteCache::most_recent_account(app));
+        match account_id.and_then(|account_id| RemoteCache::load(account_id, app)) {
+            Some(cache) => CloudReport {
+                account_id,
+                files: cache.compare(&app.saves_dir()),
+            },
+            None => CloudReport {
+                account_id: None,
+                files: BTreeMap::new(),
+            },
+        }
+    }
+
+    pub fn local_only(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter(|(_, status)| **status == CloudStatus::LocalOnly)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl fmt::Display for CloudReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.account_id {
+            Some(account_id) => writeln!(f, "Steam Cloud (account {account_id}):")?,
+            None => writeln!(f, "Steam Cloud (no remotecache.vdf found):")?,
+        }
+        for (name, status) in &self.files {
+            writeln!(f, "  {name}: {status}")?;
+        }
+        Ok(())
+    }
+}
+
+impl RemoteCache {
+    pub fn path(account_id: u32, app: &SteamApp) -> PathBuf {
+        STEAM_USER_DATA_DIR
+            .join(account_id.to_string())
+            .join(app.id().to_string())
+            .join("remotecache.vdf")
+    }
+
+    /// Finds the account that most recently synced `app`'s cloud files, for when we don't
+    /// know which account is logged in.
+    #[instrument]
+    pub fn most_recent_account(app: &SteamApp) -> Option<u32> {
+        let entries = STEAM_USER_DATA_DIR.read_dir().ok()?;
+        entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter_map(|account_id| {
+                let modified = RemoteCache::path(account_id, app)
+                    .metadata()
+                    .ok()?
+                    .modified()
+                    .ok()?;
+                Some((modified, account_id))
+            })
+            .max()
+            .map(|(_, account_id)| account_id)
+    }
+
+    #[instrument]
+    pub fn load(account_id: u32, app: &SteamApp) -> Option<Self> {
+        let path = RemoteCache::path(account_id, app);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        trace!("Read {path:?}");
+        Some(RemoteCache::parse(&contents))
+    }
+
+    /// Reads a `remotecache.vdf`. One Steam is halfway through writing, or that's otherwise
+    /// malformed, is logged and treated as empty, so it can't stop a sync.
+    pub fn parse(contents: &str) -> Self {
+        let vdf = match Vdf::parse(contents) {
+            Ok(vdf) => vdf,
+            Err(err) => {
+                warn!("Ignoring malformed remotecache.vdf: {err}");
+                return RemoteCache::default();
+            }
+        };
+        let Some(root) = vdf.value.get_obj() else {
+            return RemoteCache::default();
+        };
+
+        let mut cache = RemoteCache {
+            change_number: get_parsed(root, "ChangeNumber"),
+            files: BTreeMap::new(),
+        };
+
+        for (name, values) in root.iter() {
+            let Some(file) = values.first().and_then(|value| value.get_obj()) else {
+                continue;
+            };
+            cache.files.insert(
+                name.to_string(),
+                RemoteFile {
+                    size: get_parsed(file, "size").unwrap_or_default(),
+                    sha: get_str(file, "sha").unwrap_or_default().to_lowercase(),
+                    sync_state: get_parsed(file, "syncstate").unwrap_or_default(),
+                    local_time: get_parsed(file, "localtime").unwrap_or_default(),
+                    remote_time: get_parsed(file, "remotetime").unwrap_or_default(),
+                },
+            );
+        }
+
+        cache
+    }
+
+    /// The cache entry for a file, matching on file name since Steam prefixes cloud paths
+    /// with the app's save root.
+    pub fn file(&self, file_name: &str) -> Option<&RemoteFile> {
+        self.files
+            .iter()
+            .find(|(name, _)| name.rsplit(['/', '\\']).next() == Some(file_name))
+            .map(|(_, file)| file)
+    }
+
+    /// Compares every `*.celeste` file in `saves_dir` against the cache. There may be no
+    /// saves directory yet on a first run, and a file can vanish while the game rewrites it,
+    /// so anything that can't be read is left out.
+    #[instrument(skip(self))]
+    pub fn compare(&self, saves_dir: &Path) -> BTreeMap<String, CloudStatus> {
+        let mut statuses = BTreeMap::new();
+
+        let entries = saves_dir.read_dir().into_iter().flatten();
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if !path.extension().map(|s| s == "celeste").unwrap_or(false) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let status = match self.file(&name) {
+                None => CloudStatus::LocalOnly,
+                Some(remote) => {
+                    let (Ok(contents), Ok(metadata)) = (std::fs::read(&path), entry.metadata())
+                    else {
+                        trace!("Couldn't read {path:?}, leaving it out");
+                        continue;
+                    };
+                    let sha = sha1_smol::Sha1::from(&contents).digest().to_string();
+                    let matches = sha == remote.sha && contents.len() as u64 == remote.size;
+                    if matches && remote.sync_state == SYNCED {
+                        CloudStatus::InSync
+                    } else if matches {
+                        // Steam has hashed this version but not uploaded it yet.
+                        CloudStatus::LocalNewer
+                    } else {
+                        let modified = metadata
+                            .modified()
+                            .ok()
+                            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                            .unwrap_or_default()
+                            .as_secs();
+                        if remote.remote_time > modified {
+                            CloudStatus::CloudNewer
+                        } else {
+                            CloudStatus::LocalNewer
+                        }
+                    }
+                }
+            };
+            debug!("{name}: {status}");
+            statuses.insert(name, status);
+        }
+
+        for name in self.files.keys() {
+            let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+            if name.ends_with(".celeste") && !statuses.contains_key(name) {
+                statuses.insert(name.to_string(), CloudStatus::CloudOnly);
+            }
+        }
+
+        statuses
+    }
+}
+
+fn get_str<'a>(obj: &'a Obj, key: &str) -> Option<&'a str> {
+    obj.get(key)?.first()?.get_str()
+}
+
+fn get_parsed<T: std::str::FromStr>(obj: &Obj, key: &str) -> Option<T> {
+    get_str(obj, key)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::scratch_dir;
+
+    /// A cache for a `0.celeste` holding `contents` that was uploaded at `remote_time`, and
+    /// for a `1.celeste` uploaded from elsewhere.
+    fn remotecache(contents: &str, sync_state: u32, remote_time: u64) -> String {
+        format!(
+            r#""504230"
+{{
+	"ChangeNumber"		"12"
+	"ostype"		"-184"
+	"Saves/0.celeste"
+	{{
+		"root"		"0"
+		"size"		"{}"
+		"localtime"		"1680000000"
+		"remotetime"		"{remote_time}"
+		"sha"		"{}"
+		"syncstate"		"{sync_state}"
+	}}
+	"Saves/1.celeste"
+	{{
+		"size"		"3"
+		"remotetime"		"1680000000"
+		"sha"		"0000000000000000000000000000000000000000"
+		"syncstate"		"1"
+	}}
+}}
+"#,
+            contents.len(),
+            sha1_smol::Sha1::from(contents)
+                .digest()
+                .to_string()
+                .to_uppercase(),
+        )
+    }
+
+    #[test]
+    fn parses_files() {
+        let cache = RemoteCache::parse(&remotecache("<SaveData />", SYNCED, 1));
+        assert_eq!(cache.change_number, Some(12));
+        assert_eq!(cache.files.len(), 2);
+        let file = cache.file("0.celeste").unwrap();
+        assert_eq!(file.size, 12);
+        assert_eq!(file.sync_state, SYNCED);
+        assert_eq!(file.local_time, 1_680_000_000);
+        assert_eq!(
+            file.sha,
+            sha1_smol::Sha1::from("<SaveData />").digest().to_string()
+        );
+        assert!(cache.file("2.celeste").is_none());
+    }
+
+    #[test]
+    fn ignores_malformed_caches() {
+        let cache = RemoteCache::parse("\"504230\"\n{\n\t\"Saves/0.celeste\"\n\t{");
+        assert!(cache.files.is_empty());
+        assert_eq!(cache.change_number, None);
+    }
+
+    #[test]
+    fn compares_saves() {
+        let dir = scratch_dir("steam-cloud");
+        std::fs::write(dir.join("0.celeste"), "<SaveData />").unwrap();
+        std::fs::write(dir.join("2.celeste"), "new").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a save").unwrap();
+
+        let statuses = |contents: &str, sync_state: u32, remote_time: u64| {
+            RemoteCache::parse(&remotecache(contents, sync_state, remote_time))
+                .compare(&dir)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        let expected = |zero: CloudStatus| {
+            vec![
+                ("0.celeste".to_string(), zero),
+                ("1.celeste".to_string(), CloudStatus::CloudOnly),
+                ("2.celeste".to_string(), CloudStatus::LocalOnly),
+            ]
+        };
+        assert_eq!(
+            statuses("<SaveData />", SYNCED, 1),
+            expected(CloudStatus::InSync)
+        );
+        assert_eq!(
+            statuses("<SaveData />", 0, 1),
+            expected(CloudStatus::LocalNewer)
+        );
+        assert_eq!(
+            statuses("<SaveData>older</SaveData>", SYNCED, 1),
+            expected(CloudStatus::LocalNewer)
+        );
+        assert_eq!(
+            statuses("<SaveData>from the deck</SaveData>", SYNCED, u64::MAX),
+            expected(CloudStatus::CloudNewer)
+        );
+    }
+
+    #[test]
+    fn compares_without_a_saves_dir() {
+        let dir = scratch_dir("steam-cloud-missing").join("Saves");
+        let statuses = RemoteCache::parse(&remotecache("", SYNCED, 1)).compare(&dir);
+        assert_eq!(statuses.len(), 2);
+        assert!(statuses
+            .values()
+            .all(|status| *status == CloudStatus::CloudOnly));
+    }
+}
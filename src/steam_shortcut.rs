@@ -0,0 +1,418 @@
+use bstr::BString;
+use bstr::ByteSlice;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::info;
+use tracing::instrument;
+use tracing::trace;
+use tracing::warn;
+use tracing_unwrap::ResultExt;
+
+use crate::binary_vdf;
+use crate::binary_vdf::Value;
+use crate::steam_app::steam_is_running;
+
+/// A non-Steam game entry in Steam's library.
+#[derive(Debug, Clone)]
+pub struct Shortcut {
+    pub app_name: String,
+    pub exe: PathBuf,
+    pub start_dir: PathBuf,
+    pub icon: PathBuf,
+}
+
+impl Shortcut {
+    /// Steam quotes the executable and start directory paths.
+    fn quoted_exe(&self) -> String {
+        format!("\"{}\"", self.exe.display())
+    }
+
+    /// The ID Steam derives for this shortcut, which also names its artwork in
+    /// `config/grid`.
+    pub fn app_id(&self) -> u32 {
+        crc32(format!("{}{}", self.quoted_exe(), self.app_name).as_bytes()) | 0x8000_0000
+    }
+
+    fn matches(&self, entry: &Value) -> bool {
+        entry.get("appid").and_then(Value::as_int) == Some(self.app_id())
+            || entry.get("Exe").and_then(Value::as_str) == Some(&self.quoted_exe())
+    }
+
+    fn update(&self, entry: &mut Value) {
+        let string = |s: String| Value::String(BString::from(s));
+        entry.set("appid", Value::Int(self.app_id()));
+        entry.set("AppName", string(self.app_name.clone()));
+        entry.set("Exe", string(self.quoted_exe()));
+        entry.set(
+            "StartDir",
+            string(format!("\"{}\"", self.start_dir.display())),
+        );
+        entry.set("icon", string(self.icon.display().to_string()));
+        for (key, default) in [
+            ("ShortcutPath", string(String::new())),
+            ("LaunchOptions", string(String::new())),
+            ("IsHidden", Value::Int(0)),
+            ("AllowDesktopConfig", Value::Int(1)),
+            ("AllowOverlay", Value::Int(1)),
+            ("OpenVR", Value::Int(0)),
+            ("LastPlayTime", Value::Int(0)),
+            ("tags", Value::Map(Vec::new())),
+        ] {
+            if entry.get(key).is_none() {
+                entry.set(key, default);
+            }
+        }
+    }
+
+    /// Adds or updates this shortcut in one account's `shortcuts.vdf`, and writes its
+    /// artwork into the account's `config/grid`. Returns the paths written.
+    #[instrument(skip(artwork))]
+    pub fn install(
+        &self,
+        user_config_dir: &Path,
+        artwork: &[(&str, &[u8])],
+    ) -> Result<Vec<PathBuf>, String> {
+        let mut written = Vec::new();
+
+        let path = user_config_dir.join("shortcuts.vdf");
+        // Read again now Steam has stopped, since it rewrites the file as it exits.
+        let mut root = match read_shortcuts(user_config_dir)? {
+            Some((bytes, root)) => {
+                back_up(&path, &bytes)?;
+                root
+            }
+            None => Value::Map(Vec::new()),
+        };
+        if root.get("shortcuts").is_none() {
+            root.set("shortcuts", Value::Map(Vec::new()));
+        }
+
+        let shortcuts = shortcuts_mut(&mut root)
+            .ok_or_else(|| format!("The shortcuts in {path:?} aren't a list"))?;
+        match shortcuts.iter_mut().find(|(_, entry)| self.matches(entry)) {
+            Some((_, entry)) => {
+                info!("Updating existing shortcut in {path:?}");
+                self.update(entry);
+            }
+            None => {
+                info!("Adding shortcut to {path:?}");
+                let index = shortcuts
+                    .iter()
+                    .filter_map(|(key, _)| key.to_str().ok()?.parse::<usize>().ok())
+                    .max()
+                    .map_or(0, |index| index + 1)
+                    .to_string();
+                let mut entry = Value::Map(Vec::new());
+                self.update(&mut entry);
+                shortcuts.push((index.into(), entry));
+            }
+        }
+
+        write_atomically(&path, &binary_vdf::serialize(&root))?;
+        written.push(path);
+
+        let grid_dir = user_config_dir.join("grid");
+        fs::create_dir_all(&grid_dir).map_err(|err| format!("Can't create {grid_dir:?}: {err}"))?;
+        for (path, (_, bytes)) in self
+            .artwork_paths(user_config_dir, artwork)
+            .into_iter()
+            .zip(artwork)
+        {
+            fs::write(&path, bytes).map_err(|err| format!("Can't write {path:?}: {err}"))?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    /// Removes this shortcut from one account's `shortcuts.vdf` and deletes its artwork.
+    /// Returns a description of each thing removed.
+    #[instrument(skip(artwork))]
+    pub fn uninstall(
+        &self,
+        user_config_dir: &Path,
+        artwork: &[(&str, &[u8])],
+    ) -> Result<Vec<String>, String> {
+        let mut removed = Vec::new();
+
+        let path = user_config_dir.join("shortcuts.vdf");
+        if let Some((bytes, mut root)) = read_shortcuts(user_config_dir)? {
+            if root.get("shortcuts").is_some() {
+                let shortcuts = shortcuts_mut(&mut root)
+                    .ok_or_else(|| format!("The shortcuts in {path:?} aren't a list"))?;
+                let before = shortcuts.len();
+                shortcuts.retain(|(_, entry)| !self.matches(entry));
+                if shortcuts.len() < before {
+                    // Steam expects the entries to be numbered consecutively.
+                    for (index, (key, _)) in shortcuts.iter_mut().enumerate() {
+                        *key = index.to_string().into();
+                    }
+                    back_up(&path, &bytes)?;
+                    write_atomically(&path, &binary_vdf::serialize(&root))?;
+                    removed.push(format!("Steam shortcut {} from {path:?}", self.app_id()));
+                }
+            }
+        }
+
+        for path in self.artwork_paths(user_config_dir, artwork) {
+            if fs::remove_file(&path).is_ok() {
+                removed.push(format!("{path:?}"));
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Where each of the `0*`-named artwork assets goes for this shortcut's app ID.
+    pub fn artwork_paths(&self, user_config_dir: &Path, artwork: &[(&str, &[u8])]) -> Vec<PathBuf> {
+        let grid_dir = user_config_dir.join("grid");
+        artwork
+            .iter()
+            .map(|(asset, _)| {
+                let suffix = asset.strip_prefix('0').unwrap_or(asset);
+                grid_dir.join(format!("{}{suffix}", self.app_id()))
+            })
+            .collect()
+    }
+}
+
+fn shortcuts_mut(root: &mut Value) -> Option<&mut Vec<(BString, Value)>> {
+    let Value::Map(entries) = root else {
+        return None;
+    };
+    entries
+        .iter_mut()
+        .find(|(key, _)| key.eq_ignore_ascii_case(b"shortcuts"))
+        .and_then(|(_, shortcuts)| shortcuts.as_map_mut())
+}
+
+/// Reads and parses one account's `shortcuts.vdf`, if it has one. Every account's is checked
+/// this way before Steam is stopped, so a file we can't make sense of leaves Steam running and
+/// the file untouched.
+pub fn read_shortcuts(user_config_dir: &Path) -> Result<Option<(Vec<u8>, Value)>, String> {
+    let path = user_config_dir.join("shortcuts.vdf");
+    match fs::read(&path) {
+        Ok(bytes) => {
+            let root =
+                binary_vdf::parse(&bytes).map_err(|err| format!("Can't parse {path:?}: {err}"))?;
+            Ok(Some((bytes, root)))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("Can't read {path:?}: {err}")),
+    }
+}
+
+/// Keeps the file as it was before we first touched it in `shortcuts.vdf.bak`. Later installs
+/// leave that backup alone, since it's the only copy from before.
+fn back_up(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let backup = path.with_extension("vdf.bak");
+    if backup.exists() {
+        return Ok(());
+    }
+    fs::write(&backup, bytes).map_err(|err| format!("Can't write {backup:?}: {err}"))
+}
+
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let temp = path.with_extension("vdf.tmp");
+    fs::write(&temp, bytes)
+        .and_then(|()| fs::rename(&temp, path))
+        .map_err(|err| format!("Can't write {path:?}: {err}"))
+}
+
+/// The `userdata/<id>/config` directories of every account that has used Steam here.
+pub fn user_config_dirs(steam_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = steam_dir.join("userdata").read_dir() else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let account_id = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let config_dir = entry.path().join("config");
+            (account_id != 0 && config_dir.is_dir()).then_some(config_dir)
+        })
+        .collect()
+}
+
+/// Steam rewrites `shortcuts.vdf` from memory when it exits, so it must not be running while
+/// we edit it. Returns whether Steam was running, so it can be restarted afterwards.
+#[instrument]
+pub fn stop_steam() -> bool {
+    if !steam_is_running() {
+        trace!("Steam isn't running");
+        return false;
+    }
+
+    info!("Asking Steam to shut down so it doesn't overwrite our changes.");
+    Command::new("steam")
+        .arg("-shutdown")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap_or_log();
+
+    let start = Instant::now();
+    while steam_is_running() {
+        if start.elapsed() > Duration::from_secs(64) {
+            panic!("Steam is still running; please exit it and try again");
+        }
+        sleep(Duration::from_millis(1024));
+    }
+    info!("Steam has shut down after {:?}", start.elapsed());
+
+    true
+}
+
+pub fn start_steam() {
+    info!("Restarting Steam.");
+    if let Err(error) = Command::new("steam")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        warn!("Failed to restart Steam: {error}");
+    }
+}
+
+/// The CRC-32 (IEEE) checksum Steam uses to derive shortcut IDs.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::scratch_dir;
+
+    fn shortcut() -> Shortcut {
+        Shortcut {
+            app_name: "Celeste Saves".to_string(),
+            exe: "/home/alice/.local/bin/celeste-saves".into(),
+            start_dir: "/home/alice/.local/bin".into(),
+            icon: "/home/alice/.config/celeste-saves/0_icon.png".into(),
+        }
+    }
+
+    fn entries(dir: &Path) -> Vec<(String, Option<String>)> {
+        let root = binary_vdf::parse(&fs::read(dir.join("shortcuts.vdf")).unwrap()).unwrap();
+        let Some(Value::Map(shortcuts)) = root.get("shortcuts") else {
+            panic!("no shortcuts map");
+        };
+        shortcuts
+            .iter()
+            .map(|(key, entry)| {
+                (
+                    key.to_string(),
+                    entry
+                        .get("AppName")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn app_id() {
+        // Steam's shortcut ID is the CRC-32 of the quoted exe and the name with the top bit
+        // set; this one was worked out with zlib.crc32 as a cross-check.
+        assert_eq!(shortcut().app_id(), 0xF9B2_92F1);
+    }
+
+    #[test]
+    fn installing_twice_leaves_one_entry() {
+        let dir = scratch_dir("shortcut-install");
+        fs::write(
+            dir.join("shortcuts.vdf"),
+            include_bytes!("../testdata/shortcuts.vdf"),
+        )
+        .unwrap();
+
+        shortcut().install(&dir, &[]).unwrap();
+        shortcut().install(&dir, &[]).unwrap();
+
+        assert_eq!(
+            entries(&dir),
+            [
+                ("0".to_string(), Some("Firefox".to_string())),
+                ("1".to_string(), Some("RetroArch".to_string())),
+                ("2".to_string(), Some("Celeste Saves".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn uninstalling_renumbers_the_rest() {
+        let dir = scratch_dir("shortcut-uninstall");
+        let mut root = binary_vdf::parse(include_bytes!("../testdata/shortcuts.vdf")).unwrap();
+        let mut ours = Value::Map(Vec::new());
+        shortcut().update(&mut ours);
+        shortcuts_mut(&mut root)
+            .unwrap()
+            .insert(1, ("1".into(), ours));
+        shortcuts_mut(&mut root).unwrap()[2].0 = "2".into();
+        fs::write(dir.join("shortcuts.vdf"), binary_vdf::serialize(&root)).unwrap();
+
+        let removed = shortcut().uninstall(&dir, &[]).unwrap();
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            entries(&dir),
+            [
+                ("0".to_string(), Some("Firefox".to_string())),
+                ("1".to_string(), Some("RetroArch".to_string())),
+            ]
+        );
+        assert!(shortcut().uninstall(&dir, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_first_backup() {
+        let dir = scratch_dir("shortcut-backup");
+        let original = include_bytes!("../testdata/shortcuts.vdf");
+        fs::write(dir.join("shortcuts.vdf"), original).unwrap();
+
+        shortcut().install(&dir, &[]).unwrap();
+        shortcut().install(&dir, &[]).unwrap();
+        shortcut().uninstall(&dir, &[]).unwrap();
+
+        assert_eq!(fs::read(dir.join("shortcuts.vdf.bak")).unwrap(), original);
+    }
+
+    #[test]
+    fn leaves_unparsable_files_alone() {
+        let dir = scratch_dir("shortcut-unparsable");
+        let truncated = &include_bytes!("../testdata/shortcuts.vdf")[..40];
+        fs::write(dir.join("shortcuts.vdf"), truncated).unwrap();
+
+        assert!(read_shortcuts(&dir).is_err());
+        assert!(shortcut().install(&dir, &[]).is_err());
+        assert!(shortcut().uninstall(&dir, &[]).is_err());
+        assert_eq!(fs::read(dir.join("shortcuts.vdf")).unwrap(), truncated);
+        assert!(!dir.join("shortcuts.vdf.bak").exists());
+    }
+}
@@ -0,0 +1,170 @@
+use keyvalues_parser::Vdf;
+use steamid_ng::SteamID;
+use tracing::instrument;
+use tracing::trace;
+use tracing::warn;
+use tracing_unwrap::ResultExt;
+
+use crate::dirs::STEAM_DIR;
+
+/// A Steam account that has logged in on this machine, from `config/loginusers.vdf`.
+#[derive(Debug, Clone)]
+pub struct SteamUser {
+    pub steam_id: SteamID,
+    pub account_name: String,
+    pub persona_name: String,
+    pub most_recent: bool,
+}
+
+impl SteamUser {
+    #[instrument]
+    pub fn all() -> Vec<SteamUser> {
+        let path = STEAM_DIR.join("config").join("loginusers.vdf");
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            trace!("No {path:?}");
+            return Vec::new();
+        };
+        let vdf = match Vdf::parse(&contents) {
+            Ok(vdf) => vdf,
+            Err(err) => {
+                warn!("Ignoring malformed {path:?}: {err}");
+                return Vec::new();
+            }
+        };
+        let Some(users) = vdf.value.get_obj() else {
+            return Vec::new();
+        };
+
+        users
+            .iter()
+            .filter_map(|(steam_id, values)| {
+                let user = values.first()?.get_obj()?;
+                let get = |key: &str| {
+                    user.get(key)
+                        .and_then(|values| values.first())
+                        .and_then(|value| value.get_str())
+                        .map(|s| s.to_string())
+                };
+                Some(SteamUser {
+                    steam_id: SteamID::from(steam_id.parse::<u64>().ok()?),
+                    account_name: get("AccountName")?,
+                    persona_name: get("PersonaName").unwrap_or_default(),
+                    most_recent: get("MostRecent").as_deref() == Some("1"),
+                })
+            })
+            .collect()
+    }
+
+    /// Finds the user with the given login name, as in Steam's `SteamUser` variable.
+    pub fn by_account_name(account_name: &str) -> Option<SteamUser> {
+        SteamUser::all()
+            .into_iter()
+            .find(|user| user.account_name.eq_ignore_ascii_case(account_name))
+    }
+
+    /// The user Steam will log in as by default.
+    pub fn most_recent() -> Option<SteamUser> {
+        SteamUser::all().into_iter().find(|user| user.most_recent)
+    }
+
+    /// The 32-bit ID used for this account's `userdata` directory.
+    pub fn account_id(&self) -> u32 {
+        self.steam_id.account_id()
+    }
+
+    /// The branch this account's saves go on, keyed on the account ID since persona names
+    /// can be shared between accounts and changed at any time. These sit next to the plain
+    /// `celeste` branch used when we don't know the account, rather than under it, since git
+    /// can't have both `celeste` and `celeste/...` branches.
+    pub fn branch(&self) -> String {
+        format!("celeste-{}", self.account_id())
+    }
+
+    /// Who commits are attributed to when the repo has no identity of its own: the persona
+    /// name, or the account name or SteamID if git won't take it, like when it's empty.
+    pub fn signature(&self) -> git2::Signature<'static> {
+        let steam_id: u64 = self.steam_id.into();
+        let email = format!("{steam_id}@steamcommunity.com");
+        [self.persona_name.as_str(), self.account_name.as_str()]
+            .into_iter()
+            .find_map(|name| git2::Signature::now(name, &email).ok())
+            .unwrap_or_else(|| git2::Signature::now(&steam_id.to_string(), &email).unwrap_or_log())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::scratch_dir;
+
+    fn user(account_id: u32, account_name: &str, persona_name: &str) -> SteamUser {
+        SteamUser {
+            steam_id: SteamID::from(76561197960265728 + u64::from(account_id)),
+            account_name: account_name.to_string(),
+            persona_name: persona_name.to_string(),
+            most_recent: true,
+        }
+    }
+
+    #[test]
+    fn branch_names() {
+        assert_eq!(user(22202, "alice", "Alice").branch(), "celeste-22202");
+        assert_eq!(user(22202, "alice", "Alice B/C").branch(), "celeste-22202");
+        // Two accounts going by the same name still get their own branches.
+        assert_eq!(user(31337, "alice2", "Alice").branch(), "celeste-31337");
+    }
+
+    #[test]
+    fn signatures() {
+        let signature = user(22202, "alice", "Alice").signature();
+        assert_eq!(signature.name(), Some("Alice"));
+        assert_eq!(
+            signature.email(),
+            Some("76561197960287930@steamcommunity.com")
+        );
+        assert_eq!(user(22202, "alice", "").signature().name(), Some("alice"));
+        assert_eq!(
+            user(22202, "", "<>").signature().name(),
+            Some("76561197960287930")
+        );
+    }
+
+    #[test]
+    fn branches_coexist_with_celeste() {
+        let repo = git2::Repository::init_bare(scratch_dir("branches")).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        for branch in [
+            "celeste".to_string(),
+            user(22202, "alice", "Alice").branch(),
+            user(31337, "alice2", "Alice").branch(),
+        ] {
+            repo.commit(
+                Some(&format!("refs/heads/{branch}")),
+                &signature,
+                &signature,
+                "sync",
+                &tree,
+                &[],
+            )
+            .unwrap();
+        }
+
+        let mut refs = repo
+            .references()
+            .unwrap()
+            .map(|reference| reference.unwrap().name().unwrap().to_string())
+            .collect::<Vec<_>>();
+        refs.sort();
+        assert_eq!(
+            refs,
+            [
+                "refs/heads/celeste",
+                "refs/heads/celeste-22202",
+                "refs/heads/celeste-31337"
+            ]
+        );
+    }
+}
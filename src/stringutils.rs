@@ -5,6 +5,7 @@ pub trait StringUtils {
     fn invert(&self) -> String;
     fn pad_start(&self, len: usize) -> String;
     fn pad_end(&self, len: usize) -> String;
+    fn strip_tags(&self) -> String;
 }
 
 impl StringUtils for &str {
@@ -37,6 +38,20 @@ impl StringUtils for &str {
     fn pad_end(&self, len: usize) -> String {
         format!("{self:<0len$}")
     }
+
+    fn strip_tags(&self) -> String {
+        let mut text = String::new();
+        let mut in_tag = false;
+        for c in self.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                c if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        html_escape::decode_html_entities(&text).into_owned()
+    }
 }
 impl StringUtils for String {
     fn color(&self, color: Color) -> String {
@@ -60,6 +75,10 @@ impl StringUtils for String {
     fn pad_end(&self, len: usize) -> String {
         self.as_str().pad_end(len)
     }
+
+    fn strip_tags(&self) -> String {
+        self.as_str().strip_tags()
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -87,7 +106,7 @@ pub enum Color {
 pub use self::Color::*;
 
 impl Color {
-    fn css_color(self) -> &'static str {
+    pub fn css_color(self) -> &'static str {
         match self {
             Default => "default",
             Black => "#000",
@@ -0,0 +1,396 @@
+use git2::BranchType;
+use git2::Oid;
+use git2::Repository;
+use git2::Tree;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+use tracing::info;
+use tracing::instrument;
+use tracing::trace;
+use tracing::warn;
+use tracing::Span;
+use tracing_unwrap::OptionExt;
+use tracing_unwrap::ResultExt;
+
+use crate::celeste_stats::parse_slot;
+use crate::compare;
+use crate::compare::{Comparison, Entrant, COMPARE_FILE};
+use crate::config::{Config, Friend};
+use crate::dirs::GIT_DIR;
+use crate::game_stats;
+use crate::git_repo;
+use crate::goldens::GoldenReport;
+use crate::goldens::GOLDENS_FILE;
+use crate::history::{render_history, slot_versions, HISTORY_FILE};
+use crate::leaderboard;
+use crate::leaderboard::{Leaderboard, Player, LEADERBOARD_FILE};
+use crate::lock;
+use crate::lock::Phase;
+use crate::logs;
+use crate::notify;
+use crate::notify::Urgency;
+use crate::sessions::Launch;
+use crate::sessions::SessionRecord;
+use crate::sessions::SESSIONS_FILE;
+use crate::settle::settled_saves;
+use crate::steam_app::CELESTE;
+use crate::steam_cloud::CloudReport;
+use crate::steam_user::SteamUser;
+
+/// Everything needed to record one play session's saves in git: created before the game
+/// starts, and finished after it exits.
+pub struct SyncSession {
+    pub repo: Repository,
+    pub config: Config,
+    pub user: Option<SteamUser>,
+    pub branch_name: String,
+    /// The Steam Cloud state of the saves before the game started.
+    pub cloud: CloudReport,
+    /// Tags this session's log lines and its record in `sessions.jsonl`.
+    pub id: String,
+    /// Everything logged about this session, including by other threads, belongs in here.
+    pub span: Span,
+    /// The branch's commit before the session.
+    pub before: Option<Oid>,
+    /// How and when the game started, once it has.
+    pub started: Option<(SystemTime, Launch)>,
+    /// When the game exited, until the session has been recorded.
+    pub ended: Option<SystemTime>,
+}
+
+impl SyncSession {
+    #[instrument]
+    pub fn new(user: Option<SteamUser>) -> Self {
+        let id = logs::session_id();
+        let span = logs::session_span(&id);
+        let _span = span.enter();
+
+        let branch_name = match &user {
+            Some(user) => user.branch(),
+            None => "celeste".to_string(),
+        };
+        info!("Syncing as {user:?} to git branch {branch_name:?}");
+
+        let repo = git_repo();
+        let config = Config::load(&repo);
+        let before = repo
+            .find_branch(&branch_name, BranchType::Local)
+            .ok()
+            .and_then(|branch| branch.get().target());
+
+        // Celeste will use existing save files from the disk if no save file exists in that
+        // slot in the cloud, so a save left behind by another account would silently be
+        // adopted by this one. Steam's remotecache.vdf tells us which files this account's
+        // cloud knows.
+        let cloud = CloudReport::get(&CELESTE, user.as_ref().map(SteamUser::account_id));
+        for name in cloud.local_only() {
+            warn!("{name} isn't in this account's Steam Cloud; it may belong to another account");
+        }
+
+        SyncSession {
+            repo,
+            config,
+            user,
+            branch_name,
+            cloud,
+            id,
+            span: span.clone(),
+            before,
+            started: None,
+            ended: None,
+        }
+    }
+
+    pub fn game_started(&mut self, launch: Launch) {
+        self.started = Some((SystemTime::now(), launch));
+    }
+
+    /// Marks the game as exited, so the next [`finish`](Self::finish) records the session.
+    pub fn game_exited(&mut self) {
+        self.ended = Some(SystemTime::now());
+    }
+
+    pub fn fetch(&self) {
+        let _span = self.span.enter();
+        if let Ok(mut _origin) = self.repo.find_remote("origin") {
+            info!("Pulling changes from remote origin");
+            let mut cmd = std::process::Command::new("git");
+            cmd.arg("fetch");
+            cmd.arg("--verbose");
+            cmd.arg("origin");
+            cmd.env("GIT_DIR", &*GIT_DIR);
+            cmd.status().unwrap_or_log();
+        } else {
+            trace!("No origin remote found, not pulling");
+        }
+
+        for friend in &self.config.friends {
+            fetch_friend(&GIT_DIR, friend);
+        }
+    }
+
+    /// Commits the current saves and their rendered stats, and pushes them if anything
+    /// changed. Returns the new commit, if any.
+    #[instrument(skip(self))]
+    pub fn finish(&mut self) -> Option<Oid> {
+        let _span = self.span.enter();
+        info!("Waiting for save files to settle.");
+        lock::set_phase(Phase::Settling);
+
+        let repo = &self.repo;
+        let branch_name = &self.branch_name;
+
+        let mut files = settled_saves(&CELESTE, self.cloud.account_id, self.config.settle_timeout);
+
+        let cloud = CloudReport::get(&CELESTE, self.cloud.account_id);
+        info!("{cloud}");
+
+        let mut generated = BTreeMap::new();
+        for (name, body) in files.iter() {
+            let name = name.to_str().unwrap_or_log();
+            let Ok(body) = std::str::from_utf8(body) else {
+                trace!("{name:?} isn't text, committing it as-is");
+                continue;
+            };
+            if let Some(rendered) = game_stats::render(&self.config, name, body) {
+                generated.insert(name.replace(".celeste", ".html"), rendered.html);
+                generated.insert(name.replace(".celeste", ".json"), rendered.json);
+            } else {
+                trace!("Not rendering {name:?}, committing it as-is");
+            }
+        }
+
+        for (name, body) in generated.into_iter() {
+            files.insert(name.into(), body.into_bytes());
+        }
+
+        let branch = repo.find_branch(branch_name, BranchType::Local).ok();
+        let existing_tree = branch
+            .as_ref()
+            .map(|b| b.get().peel_to_tree().unwrap_or_log());
+
+        let saves = files
+            .iter()
+            .filter_map(|(name, body)| {
+                Some((
+                    name.to_str()?.to_string(),
+                    String::from_utf8(body.clone()).ok()?,
+                ))
+            })
+            .collect();
+        let head = branch.as_ref().and_then(|b| b.get().target());
+        let versions = slot_versions(repo, head, &saves);
+        let goldens = GoldenReport::from_versions(&versions);
+        if !goldens.slots.is_empty() {
+            files.insert(GOLDENS_FILE.into(), goldens.render_html().into_bytes());
+            files.insert(HISTORY_FILE.into(), render_history(&versions).into_bytes());
+        }
+
+        let entrants = saves
+            .iter()
+            .filter_map(|(name, contents)| {
+                let stats = parse_slot(name, contents)?;
+                Some(Entrant {
+                    label: compare::label(&stats, name),
+                    stats,
+                })
+            })
+            .collect::<Vec<_>>();
+        if entrants.len() > 1 {
+            let comparison = Comparison::new(entrants.clone(), &self.config);
+            files.insert(COMPARE_FILE.into(), comparison.render_html().into_bytes());
+        }
+
+        if !self.config.friends.is_empty() {
+            let mut players = vec![Player {
+                name: leaderboard::own_name(self.user.as_ref()),
+                slots: entrants.into_iter().map(|entrant| entrant.stats).collect(),
+            }];
+            players.extend(leaderboard::friends(repo, &self.config));
+            let leaderboard = Leaderboard::new(&players, &self.config);
+            files.insert(
+                LEADERBOARD_FILE.into(),
+                leaderboard.render_html().into_bytes(),
+            );
+        }
+
+        let sessions = existing_file(repo, existing_tree.as_ref(), SESSIONS_FILE);
+        let sessions = match (self.started, self.ended) {
+            (Some((started, launch)), Some(ended)) => Some(
+                SessionRecord::new(
+                    &self.id,
+                    started,
+                    ended,
+                    launch,
+                    self.user.as_ref(),
+                    self.before.map(|before| before.to_string()),
+                )
+                .append_to(sessions.as_deref()),
+            ),
+            _ => sessions,
+        };
+        if let Some(sessions) = sessions {
+            files.insert(SESSIONS_FILE.into(), sessions.into_bytes());
+        }
+
+        lock::set_phase(Phase::Committing);
+        let mut tree = repo.treebuilder(None).unwrap_or_log();
+        for (name, body) in files.iter() {
+            let mut blob = repo.blob_writer(Some(name.as_ref())).unwrap_or_log();
+            blob.write_all(body).unwrap_or_log();
+            let blob = blob.commit().unwrap_or_log();
+            tree.insert(name, blob, 0o100_644).unwrap_or_log();
+        }
+        let tree = tree.write().unwrap_or_log();
+        let tree = repo.find_tree(tree).unwrap_or_log();
+
+        if Some(tree.id()) == existing_tree.as_ref().map(|t| t.id()) {
+            info!("No changes to save.");
+            return None;
+        }
+
+        let mut highlights = Vec::new();
+        for (name, body) in files.iter() {
+            let name = name.to_str().unwrap_or_log();
+            let Ok(body) = std::str::from_utf8(body) else {
+                continue;
+            };
+            let before = existing_file(repo, existing_tree.as_ref(), name);
+            highlights.extend(game_stats::highlights(
+                &self.config,
+                name,
+                before.as_deref(),
+                body,
+            ));
+        }
+
+        // TODO: if upstream doesn't match, add both as parents?
+        let parents = branch
+            .map(|b| vec![b.get().peel_to_commit().unwrap_or_log()])
+            .unwrap_or_default();
+
+        let mut message = tree.id().to_string();
+        if !highlights.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(&highlights.join("\n"));
+        }
+        if self.config.cloud_report && cloud.account_id.is_some() {
+            message.push_str(&format!("\n\n{cloud}"));
+        }
+
+        let signature = match (repo.signature(), &self.user) {
+            (Ok(signature), _) => signature,
+            (Err(_), Some(user)) => user.signature(),
+            (Err(err), None) => Err(err).unwrap_or_log(),
+        };
+        let commit = repo
+            .commit(
+                Some(&format!("refs/heads/{branch_name}")),
+                &signature,
+                &signature,
+                &message,
+                &tree,
+                parents[..].iter().collect_vec().as_ref(),
+            )
+            .unwrap_or_log();
+
+        info!("Committed {commit:?} to git branch {branch_name:?}");
+        // The session is on record now, so a later sync mustn't add it again.
+        self.ended = None;
+
+        let pushed = if let Ok(mut _origin) = repo.find_remote("origin") {
+            info!("Pushing changes to remote 'origin");
+            lock::set_phase(Phase::Pushing);
+            // We shell out instead of figuring out the auth dance.
+            let mut cmd = std::process::Command::new("git");
+            cmd.arg("push");
+            cmd.arg("--verbose");
+            cmd.arg("origin");
+            cmd.arg(format!("{branch_name}:{branch_name}"));
+            cmd.env("GIT_DIR", &*GIT_DIR);
+            let output = cmd.output().unwrap_or_log();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            info!("git push: {stderr}");
+            if output.status.success() {
+                Some(Ok(()))
+            } else {
+                warn!("Push failed with {}", output.status);
+                let error = stderr
+                    .lines()
+                    .find(|line| line.starts_with("fatal:") || line.starts_with("error:"))
+                    .or_else(|| stderr.lines().rev().find(|line| !line.trim().is_empty()));
+                Some(Err(error.unwrap_or("unknown error").trim().to_string()))
+            }
+        } else {
+            trace!("No origin remote found, not pushing");
+            None
+        };
+
+        if self.config.notify {
+            self.notify(&highlights, pushed);
+        }
+
+        Some(commit)
+    }
+
+    /// Tells the player what the session achieved and whether it made it to the remote.
+    fn notify(&self, highlights: &[String], pushed: Option<Result<(), String>>) {
+        let (summary, urgency) = match &pushed {
+            Some(Err(_)) => ("Celeste saves committed, but not pushed", Urgency::Critical),
+            _ => ("Celeste saves synced", Urgency::Normal),
+        };
+
+        // Notifications are only a few lines tall; the commit has the rest.
+        const MAX_HIGHLIGHTS: usize = 6;
+        let mut body = highlights
+            .iter()
+            .take(MAX_HIGHLIGHTS)
+            .cloned()
+            .collect_vec();
+        if highlights.len() > MAX_HIGHLIGHTS {
+            body.push(format!("...and {} more", highlights.len() - MAX_HIGHLIGHTS));
+        }
+        if body.is_empty() {
+            body.push("No new personal bests or berries.".to_string());
+        }
+        body.push(match pushed {
+            Some(Ok(())) => format!("Pushed {} to origin.", self.branch_name),
+            Some(Err(error)) => format!("Push failed: {error}"),
+            None => format!("Committed to {}; no remote to push to.", self.branch_name),
+        });
+
+        notify::send(summary, &body.join("\n"), urgency);
+    }
+}
+
+/// The contents of a file in a committed tree.
+fn existing_file(repo: &Repository, tree: Option<&Tree>, name: &str) -> Option<String> {
+    let blob = tree?
+        .get_name(name)?
+        .to_object(repo)
+        .ok()?
+        .into_blob()
+        .ok()?;
+    Some(String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+/// Fetches a friend's branches into the repo at `git_dir`, pruning any they've deleted.
+/// Returns whether it worked.
+pub fn fetch_friend(git_dir: &Path, friend: &Friend) -> bool {
+    info!("Fetching {}'s saves from {}", friend.name, friend.url);
+    let mut cmd = std::process::Command::new("git");
+    cmd.arg("fetch");
+    cmd.arg("--verbose");
+    cmd.arg("--prune");
+    cmd.arg(&friend.url);
+    cmd.arg(friend.refspec());
+    cmd.env("GIT_DIR", git_dir);
+    let status = cmd.status().unwrap_or_log();
+    if !status.success() {
+        warn!("Fetching {}'s saves failed with {status}", friend.name);
+    }
+    status.success()
+}
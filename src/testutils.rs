@@ -0,0 +1,45 @@
+//! Helpers shared by the unit tests.
+
+use std::path::PathBuf;
+
+/// An empty directory of our own under the system temp directory, named for the test and
+/// process so parallel tests and runs don't share one.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("celeste-saves-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A save slot with, for each of `areas`, the area's ID, its A-side's best time in ticks of
+/// 100ns, and the entity keys of the berries collected there.
+pub fn save_xml(name: &str, areas: &[(u32, u64, &[&str])]) -> String {
+    let total: usize = areas.iter().map(|(_, _, keys)| keys.len()).sum();
+    let areas = areas
+        .iter()
+        .map(|(id, best_time, keys)| {
+            let keys = keys
+                .iter()
+                .map(|key| format!(r#"<EntityID Key="{key}" />"#))
+                .collect::<String>();
+            format!(
+                r#"
+    <AreaStats ID="{id}" Cassette="false"><Modes>
+      <AreaModeStats Completed="true" SingleRunCompleted="true" BestTime="{best_time}" BestFullClearTime="0" BestDashes="3" BestDeaths="4" HeartGem="true"><Strawberries>{keys}</Strawberries></AreaModeStats>
+    </Modes></AreaStats>"#
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"<SaveData xmlns="">
+  <Version>1.4.0.0</Version>
+  <Name>{name}</Name>
+  <CheatMode>false</CheatMode>
+  <AssistMode>false</AssistMode>
+  <VariantMode>false</VariantMode>
+  <TotalStrawberries>{total}</TotalStrawberries>
+  <Areas>{areas}
+  </Areas>
+</SaveData>"#
+    )
+}
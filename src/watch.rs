@@ -0,0 +1,119 @@
+//! A long-running mode that syncs every Celeste session, however the game was started, for
+//! when it's launched from the Steam library rather than through our shortcut.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::debug;
+use tracing::info;
+use tracing_unwrap::ResultExt;
+
+use crate::control;
+use crate::lock;
+use crate::lock::Phase;
+use crate::sessions::Launch;
+use crate::steam_app::AppProcess;
+use crate::steam_app::CELESTE;
+use crate::steam_user::SteamUser;
+use crate::sync::SyncSession;
+
+/// How often to look for new processes while the game isn't running.
+const POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Watches for the game to start, forever, syncing each session after it exits.
+pub fn watch() -> ! {
+    info!("Watching for {:?} to start", CELESTE.name);
+    control::serve(true);
+
+    let mut seen = BTreeSet::new();
+    loop {
+        // Checking a process means reading several files from /proc, and processes start
+        // all the time, so we only check the ones that started since we last looked, and
+        // only scan them all to pick the game's main process once one of them matches.
+        let pids = pids();
+        let started = pids.difference(&seen).any(|&pid| CELESTE.is_process(pid));
+        seen = pids;
+        if started {
+            if let Some(process) = CELESTE.find_process() {
+                sync_session(process);
+                // Whatever started during the session hasn't been checked yet.
+                seen.clear();
+            }
+        }
+
+        // A wrapped or shortcut launch takes the socket over and removes it when done.
+        if !control::serving() {
+            control::serve(true);
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/// Syncs the session of a game process that has just been noticed, unless another instance
+/// is already syncing it.
+fn sync_session(process: AppProcess) {
+    info!(
+        "Noticed {:?} as PID {} (matched by {:?})",
+        process.app.name,
+        process.process.pid(),
+        process.matched_by
+    );
+
+    if let Err(holder) = lock::acquire() {
+        info!(
+            "PID {} is already syncing ({}); waiting for the game to exit.",
+            holder.pid, holder.phase
+        );
+        process.wait_for_exit();
+        return;
+    }
+
+    // Steam tells the game which account launched it.
+    let user = process
+        .process
+        .environ()
+        .ok()
+        .and_then(|environ| {
+            let account_name = environ.get(OsStr::new("SteamUser"))?.to_str()?.to_string();
+            SteamUser::by_account_name(&account_name)
+        })
+        .or_else(SteamUser::most_recent);
+
+    let session = Arc::new(Mutex::new(SyncSession::new(user)));
+    // The watcher is already serving the control socket, which acts on this session now.
+    control::set_session(Some(session.clone()));
+    let _span = session.lock().unwrap_or_log().span.clone().entered();
+    session.lock().unwrap_or_log().game_started(Launch::Noticed);
+
+    // The game already has the saves open, so fetching is only for the push afterwards.
+    lock::set_phase(Phase::Fetching);
+    session.lock().unwrap_or_log().fetch();
+
+    lock::set_phase(Phase::Watching);
+    process.wait_for_exit();
+    session.lock().unwrap_or_log().game_exited();
+
+    let start = Instant::now();
+    session.lock().unwrap_or_log().finish();
+    info!("Synced in {:?}", start.elapsed());
+
+    control::set_session(None);
+    lock::release();
+}
+
+fn pids() -> BTreeSet<i32> {
+    match procfs::process::all_processes() {
+        Ok(processes) => processes
+            .filter_map(Result::ok)
+            .map(|process| process.pid())
+            .collect(),
+        Err(err) => {
+            debug!("Couldn't list processes: {err}");
+            BTreeSet::new()
+        }
+    }
+}